@@ -1,19 +1,134 @@
 use bytes::Bytes;
+use percent_encoding::percent_decode_str;
+use servio_service::EventFamily;
+use std::borrow::Cow;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 
 pub const PROTOCOL_HTTP: &str = "http";
 pub const EVENT_HTTP: &str = "http";
 
+/// Event family of [`HttpEvent`], named [`EVENT_HTTP`].
+pub enum HttpFamily {}
+
+impl EventFamily for HttpFamily {
+    const NAME: &'static str = EVENT_HTTP;
+    type Payload = HttpEvent;
+}
+
 #[non_exhaustive]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct HttpScope {
     pub method: http::Method,
-    pub uri: http::Uri,
+    /// Request URI. It is set with [`HttpScope::set_uri`], that keeps `path`, `raw_path` and
+    /// `query_string` in sync with it.
+    uri: http::Uri,
     pub version: http::Version,
     pub headers: http::HeaderMap,
 
-    pub server: Option<SocketAddr>,
-    pub client: Option<SocketAddr>,
+    /// ASGI equivalent: `scheme`. Defaults to `http`.
+    pub scheme: Cow<'static, str>,
+    /// Percent-decoded request path, including `root_path`. ASGI equivalent: `path`
+    pub path: String,
+    /// Request path, as it was received. ASGI equivalent: `raw_path`
+    pub raw_path: Bytes,
+    /// Query string without leading `?`. ASGI equivalent: `query_string`
+    pub query_string: Bytes,
+    /// Path prefix, that the app is mounted at. ASGI equivalent: `root_path`
+    pub root_path: String,
+
+    /// ASGI equivalent: `server`
+    pub server: Option<Address>,
+    /// ASGI equivalent: `client`
+    pub client: Option<Address>,
+}
+
+/// Address of a connection side.
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Address {
+    Tcp(SocketAddr),
+    /// Unix domain socket path. Unnamed sockets, like client sides of most connections, have
+    /// no path.
+    Unix(Option<PathBuf>),
+}
+
+impl From<SocketAddr> for Address {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Tcp(addr)
+    }
+}
+
+impl Default for HttpScope {
+    fn default() -> Self {
+        Self {
+            method: Default::default(),
+            uri: Default::default(),
+            version: Default::default(),
+            headers: Default::default(),
+            scheme: "http".into(),
+            path: "/".into(),
+            raw_path: Bytes::from_static(b"/"),
+            query_string: Bytes::new(),
+            root_path: String::new(),
+            server: None,
+            client: None,
+        }
+    }
+}
+
+impl HttpScope {
+    /// Returns request URI, as it was received. Mounting doesn't change it.
+    pub fn uri(&self) -> &http::Uri {
+        &self.uri
+    }
+
+    /// Sets request URI and fills `path`, `raw_path` and `query_string` from it. If URI is
+    /// absolute, `scheme` is taken from it too.
+    pub fn set_uri(&mut self, uri: http::Uri) {
+        if let Some(scheme) = uri.scheme_str() {
+            self.scheme = scheme.to_owned().into();
+        }
+
+        let raw_path = match uri.path() {
+            "" => "/",
+            path => path,
+        };
+        self.path = percent_decode_str(raw_path)
+            .decode_utf8_lossy()
+            .into_owned();
+        self.raw_path = Bytes::copy_from_slice(raw_path.as_bytes());
+        self.query_string = uri
+            .query()
+            .map(|query| Bytes::copy_from_slice(query.as_bytes()))
+            .unwrap_or_default();
+        self.uri = uri;
+    }
+
+    /// Returns the part of `path` after `root_path`, that is the path within the mounted app.
+    pub fn app_path(&self) -> &str {
+        match self.path.strip_prefix(self.root_path.as_str()) {
+            Some("") => "/",
+            Some(path) => path,
+            None => &self.path,
+        }
+    }
+
+    /// Mounts the app at `prefix`, relative to the current `root_path`, by appending the prefix
+    /// to `root_path`. `path` is kept intact, so that it always includes `root_path`.
+    ///
+    /// Returns `false` and leaves the scope unchanged, if the app path is not under the prefix.
+    /// Routing by prefix is done by `Mount` middleware of `servio-util`.
+    pub fn mount(&mut self, prefix: &str) -> bool {
+        let prefix = prefix.trim_end_matches('/');
+        match self.app_path().strip_prefix(prefix) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                self.root_path.push_str(prefix);
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 #[non_exhaustive]
@@ -21,6 +136,11 @@ pub struct HttpScope {
 pub enum HttpEvent {
     /// ASGI equivalent: `http.request`
     RequestChunk(RequestChunk),
+    /// Trailers of a request body. ASGI has no equivalent, it mirrors `ResponseTrailer`.
+    ///
+    /// If request has trailers, the last `RequestChunk` is sent with `more` set, and the body
+    /// ends with `RequestTrailer` instead.
+    RequestTrailer(RequestTrailer),
     /// ASGI equivalent: `http.response.body`
     ResponseChunk(ResponseChunk),
     /// ASGI equivalent: `http.response.start`
@@ -38,6 +158,13 @@ pub struct RequestChunk {
     pub more: bool,
 }
 
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct RequestTrailer {
+    pub headers: http::HeaderMap,
+    pub more: bool,
+}
+
 #[non_exhaustive]
 #[derive(Default, Clone, Debug)]
 pub struct ResponseChunk {
@@ -1,3 +1,5 @@
 #![forbid(unsafe_code)]
 pub mod http;
+pub mod lifespan;
+pub mod tls;
 pub mod websocket;
@@ -0,0 +1,68 @@
+use servio_service::EventFamily;
+use std::borrow::Cow;
+
+pub const PROTOCOL_LIFESPAN: &str = "lifespan";
+pub const EVENT_LIFESPAN: &str = "lifespan";
+
+/// Event family of [`LifespanEvent`], named [`EVENT_LIFESPAN`].
+pub enum LifespanFamily {}
+
+impl EventFamily for LifespanFamily {
+    const NAME: &'static str = EVENT_LIFESPAN;
+    type Payload = LifespanEvent;
+}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct LifespanScope {}
+
+/// Events of lifespan protocol. Server sends `Startup` before it starts accepting connections
+/// and `Shutdown` after it stops. Application must respond to each of them with a
+/// corresponding `*Complete` or `*Failed` event.
+///
+/// If application does not support lifespan protocol, it should return an error or close
+/// the stream. Server should continue without lifespan events in this case.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum LifespanEvent {
+    /// ASGI equivalent: `lifespan.startup`
+    Startup(Startup),
+    /// ASGI equivalent: `lifespan.startup.complete`
+    StartupComplete(StartupComplete),
+    /// ASGI equivalent: `lifespan.startup.failed`
+    StartupFailed(StartupFailed),
+    /// ASGI equivalent: `lifespan.shutdown`
+    Shutdown(Shutdown),
+    /// ASGI equivalent: `lifespan.shutdown.complete`
+    ShutdownComplete(ShutdownComplete),
+    /// ASGI equivalent: `lifespan.shutdown.failed`
+    ShutdownFailed(ShutdownFailed),
+}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct Startup {}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct StartupComplete {}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct StartupFailed {
+    pub message: Cow<'static, str>,
+}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct Shutdown {}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct ShutdownComplete {}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct ShutdownFailed {
+    pub message: Cow<'static, str>,
+}
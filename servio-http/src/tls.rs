@@ -0,0 +1,23 @@
+use bytes::Bytes;
+
+/// TLS details of a connection. It is inserted into `http` and `websocket` scopes alongside
+/// protocol scope, if the connection is encrypted.
+///
+/// ASGI equivalent: `tls` (from [TLS](https://asgi.readthedocs.io/en/latest/specs/tls.html)
+/// extension). Certificates are DER-encoded, while ASGI uses PEM.
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct TlsScope {
+    /// Certificate, that server presented to the client. ASGI equivalent: `server_cert`
+    pub server_cert: Option<Bytes>,
+    /// Client certificate chain, leaf first. ASGI equivalent: `client_cert_chain`
+    pub client_cert_chain: Vec<Bytes>,
+    /// TLS version, as sent on the wire, e.g. `0x0304` for TLS 1.3. ASGI equivalent: `tls_version`
+    pub tls_version: Option<u16>,
+    /// IANA cipher suite identifier. ASGI equivalent: `cipher_suite`
+    pub cipher_suite: Option<u16>,
+    /// Protocol, negotiated with ALPN. ASGI has no equivalent.
+    pub alpn_protocol: Option<Bytes>,
+    /// Server name, requested by the client with SNI. ASGI has no equivalent.
+    pub server_name: Option<String>,
+}
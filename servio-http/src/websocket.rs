@@ -1,15 +1,43 @@
 use bytes::Bytes;
+use servio_service::EventFamily;
 use std::borrow::Cow;
 
 pub const PROTOCOL_WEBSOCKET: &str = "websocket";
 pub const EVENT_WEBSOCKET: &str = "websocket";
 
+/// Event family of [`WebSocketEvent`], named [`EVENT_WEBSOCKET`].
+pub enum WebSocketFamily {}
+
+impl EventFamily for WebSocketFamily {
+    const NAME: &'static str = EVENT_WEBSOCKET;
+    type Payload = WebSocketEvent;
+}
+
 #[non_exhaustive]
 #[derive(Default, Clone, Debug)]
 pub struct WebSocketScope {
     pub subprotocols: Vec<Cow<'static, str>>,
+    /// Extensions, offered by the client in `Sec-WebSocket-Extensions` header, in order of
+    /// preference.
+    pub extensions: Vec<WebSocketExtension>,
+}
+
+/// WebSocket extension with its parameters, like `permessage-deflate; client_max_window_bits`.
+#[non_exhaustive]
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct WebSocketExtension {
+    pub name: Cow<'static, str>,
+    /// Parameters with optional values.
+    pub params: Vec<(Cow<'static, str>, Option<Cow<'static, str>>)>,
 }
 
+/// Events of a WebSocket connection.
+///
+/// Instead of accepting the connection, the application may deny it in response to `Connect`.
+/// `Close` event results in `403 Forbidden` response, while [`http`](crate::http) family
+/// `ResponseStart` and `ResponseChunk` events make a custom response (ASGI equivalent:
+/// [WebSocket Denial Response](https://asgi.readthedocs.io/en/latest/extensions.html#websocket-denial-response)
+/// extension). Server stream ends after the connection is denied.
 #[non_exhaustive]
 #[derive(Clone, Debug)]
 pub enum WebSocketEvent {
@@ -18,13 +46,27 @@ pub enum WebSocketEvent {
     /// ASGI equivalent: `websocket.accept`
     Accept(Accept),
     /// ASGI equivalent: `websocket.receive` and `websocket.send` with `text` field set
+    ///
+    /// A message may be split into several events, all of them but the last one with `more` set.
+    /// Each event holds valid UTF-8 text, even if the message was split inside a character.
     TextFrame(TextFrame),
     /// ASGI equivalent: `websocket.receive` and `websocket.send` with `bytes` field set
+    ///
+    /// A message may be split into several events, all of them but the last one with `more` set.
     BinaryFrame(BinaryFrame),
     /// ASGI equivalent: `websocket.disconnect`
     Disconnect(Disconnect),
     /// ASGI equivalent: `websocket.close`
+    ///
+    /// Sent before `Accept`, it denies the connection with `403 Forbidden` response.
     Close(Close),
+    /// No ASGI equivalent. Ping frame, received from the client or sent by the application.
+    ///
+    /// Servers answer pings from clients on their own, and may send them to the application only
+    /// if configured to do so.
+    Ping(Ping),
+    /// No ASGI equivalent. Pong frame, received from the client or sent by the application.
+    Pong(Pong),
 }
 
 #[non_exhaustive]
@@ -35,6 +77,9 @@ pub struct Connect {}
 #[derive(Default, Clone, Debug)]
 pub struct Accept {
     pub subprotocol: Option<Cow<'static, str>>,
+    /// Extensions, accepted by the application, with response parameters. If empty, server may
+    /// negotiate extensions, that it's configured to use.
+    pub extensions: Vec<WebSocketExtension>,
     pub headers: http::HeaderMap,
 }
 
@@ -42,12 +87,16 @@ pub struct Accept {
 #[derive(Default, Clone, Debug)]
 pub struct TextFrame {
     pub data: String,
+    /// If set, the message continues in the next `TextFrame`. No ASGI equivalent.
+    pub more: bool,
 }
 
 #[non_exhaustive]
 #[derive(Default, Clone, Debug)]
 pub struct BinaryFrame {
     pub data: Bytes,
+    /// If set, the message continues in the next `BinaryFrame`. No ASGI equivalent.
+    pub more: bool,
 }
 
 #[non_exhaustive]
@@ -77,3 +126,15 @@ impl Default for Close {
         }
     }
 }
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct Ping {
+    pub data: Bytes,
+}
+
+#[non_exhaustive]
+#[derive(Default, Clone, Debug)]
+pub struct Pong {
+    pub data: Bytes,
+}
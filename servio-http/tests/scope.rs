@@ -0,0 +1,34 @@
+use servio_http::http::HttpScope;
+
+#[test]
+fn uri_parts() {
+    let mut scope = HttpScope::default();
+    assert_eq!(scope.scheme, "http");
+    assert_eq!(scope.path, "/");
+
+    scope.set_uri(
+        "https://example.com/caf%C3%A9/a%2Fb?x=1&y=%20"
+            .parse()
+            .unwrap(),
+    );
+    assert_eq!(scope.scheme, "https");
+    assert_eq!(scope.path, "/café/a/b");
+    assert_eq!(scope.raw_path, "/caf%C3%A9/a%2Fb");
+    assert_eq!(scope.query_string, "x=1&y=%20");
+}
+
+#[test]
+fn mount() {
+    let mut scope = HttpScope::default();
+    scope.set_uri("/api/v1/users".parse().unwrap());
+
+    assert!(!scope.mount("/ap"));
+    assert!(scope.mount("/api/"));
+    assert!(scope.mount("/v1"));
+    assert_eq!(scope.root_path, "/api/v1");
+    assert_eq!(scope.path, "/api/v1/users");
+    assert_eq!(scope.app_path(), "/users");
+
+    assert!(scope.mount("/users"));
+    assert_eq!(scope.app_path(), "/");
+}
@@ -0,0 +1,216 @@
+use crate::BoxError;
+use bytes::{Buf, Bytes};
+use futures_util::future;
+use http::{Request, Response};
+use hyper::body::{Body, Incoming as IncomingBody};
+use hyper::rt::Executor;
+use hyper::server::conn::{http1, http2};
+use hyper::service::Service as HyperService;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+/// Connection preface, that is sent by HTTP/2 clients first.
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// HTTP protocol, that is used to serve a connection.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HttpProtocol {
+    /// HTTP/1.0 and HTTP/1.1.
+    Http1,
+    /// HTTP/2 with prior knowledge.
+    Http2,
+    /// HTTP/2 if connection starts with HTTP/2 preface, HTTP/1 otherwise.
+    #[default]
+    Auto,
+}
+
+impl HttpProtocol {
+    /// Returns protocol, negotiated with ALPN. Unknown protocols are served with [`Self::Auto`].
+    pub fn from_alpn(protocol: &[u8]) -> Self {
+        match protocol {
+            b"h2" => Self::Http2,
+            b"http/1.1" | b"http/1.0" => Self::Http1,
+            _ => Self::Auto,
+        }
+    }
+}
+
+/// [`Executor`], that spawns HTTP/2 streams on `tokio` runtime.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioExecutor;
+
+impl<F> Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        tokio::spawn(fut);
+    }
+}
+
+/// Serves HTTP connection with `hyper` service using specified protocol.
+///
+/// HTTP/2 streams are spawned on `tokio` runtime.
+pub async fn serve_connection<I, S, B>(
+    io: I,
+    service: S,
+    protocol: HttpProtocol,
+) -> Result<(), BoxError>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: HyperService<Request<IncomingBody>, Response = Response<B>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    serve_connection_with_shutdown(io, service, protocol, future::pending()).await
+}
+
+/// Serves HTTP connection like [`serve_connection`], starting graceful shutdown of the connection
+/// when `shutdown` future completes.
+///
+/// After graceful shutdown has started, no new requests are accepted, and the connection is
+/// closed as soon as in-flight requests are finished.
+pub async fn serve_connection_with_shutdown<I, S, B, F>(
+    io: I,
+    service: S,
+    protocol: HttpProtocol,
+    shutdown: F,
+) -> Result<(), BoxError>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: HyperService<Request<IncomingBody>, Response = Response<B>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    F: Future<Output = ()>,
+{
+    let (protocol, io) = match protocol {
+        HttpProtocol::Auto => detect_protocol(io).await?,
+        protocol => (protocol, Rewind::new(io, Bytes::new())),
+    };
+
+    match protocol {
+        HttpProtocol::Http2 => {
+            let conn = http2::Builder::new(TokioExecutor).serve_connection(io, service);
+            graceful(conn, shutdown, |conn| conn.graceful_shutdown()).await?
+        }
+        _ => {
+            let conn = http1::Builder::new()
+                .serve_connection(io, service)
+                .with_upgrades();
+            graceful(conn, shutdown, |conn| conn.graceful_shutdown()).await?
+        }
+    }
+    Ok(())
+}
+
+/// Drives connection to completion, starting its graceful shutdown when `shutdown` completes.
+async fn graceful<C, F>(conn: C, shutdown: F, start: fn(Pin<&mut C>)) -> C::Output
+where
+    C: Future,
+    F: Future<Output = ()>,
+{
+    tokio::pin!(conn);
+    tokio::pin!(shutdown);
+    let mut shutting_down = false;
+
+    loop {
+        tokio::select! {
+            result = conn.as_mut() => return result,
+            _ = shutdown.as_mut(), if !shutting_down => {
+                shutting_down = true;
+                start(conn.as_mut());
+            }
+        }
+    }
+}
+
+/// Reads the beginning of the connection, until it is known, whether it is HTTP/2 preface.
+async fn detect_protocol<I>(mut io: I) -> io::Result<(HttpProtocol, Rewind<I>)>
+where
+    I: AsyncRead + Unpin,
+{
+    let mut buf = Vec::with_capacity(H2_PREFACE.len());
+    while buf.len() < H2_PREFACE.len() && H2_PREFACE.starts_with(&buf) {
+        let mut chunk = [0; H2_PREFACE.len()];
+        let n = io.read(&mut chunk[..H2_PREFACE.len() - buf.len()]).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let protocol = if buf == H2_PREFACE {
+        HttpProtocol::Http2
+    } else {
+        HttpProtocol::Http1
+    };
+    Ok((protocol, Rewind::new(io, buf.into())))
+}
+
+/// IO, that replays already read prefix before reading from inner IO.
+struct Rewind<I> {
+    prefix: Bytes,
+    inner: I,
+}
+
+impl<I> Rewind<I> {
+    fn new(inner: I, prefix: Bytes) -> Self {
+        Self { prefix, inner }
+    }
+}
+
+impl<I: AsyncRead + Unpin> AsyncRead for Rewind<I> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.prefix.has_remaining() {
+            let n = self.prefix.len().min(buf.remaining());
+            buf.put_slice(&self.prefix[..n]);
+            self.prefix.advance(n);
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<I: AsyncWrite + Unpin> AsyncWrite for Rewind<I> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
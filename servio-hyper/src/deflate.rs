@@ -0,0 +1,363 @@
+//! `permessage-deflate` WebSocket extension ([RFC 7692](https://www.rfc-editor.org/rfc/rfc7692)).
+//!
+//! `tungstenite` doesn't support extensions, so compression is applied to the raw connection:
+//! compressed frames of the client are inflated before `tungstenite` reads them, and data frames,
+//! written by `tungstenite`, are deflated before they are sent.
+
+use crate::frame::{
+    close_error, put_header, unmask, FrameHeader, Rewrite, OPCODE_BINARY, OPCODE_CONTINUATION,
+    OPCODE_TEXT, RSV1,
+};
+use bytes::BytesMut;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use servio_http::websocket::WebSocketExtension;
+use std::borrow::Cow;
+use std::io;
+
+pub(crate) const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// Trailer of a sync flush, that is stripped from compressed messages.
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Raw deflate in `zlib` doesn't support 256-byte windows.
+const MIN_WINDOW_BITS: u8 = 9;
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Settings of `permessage-deflate` compression, that is negotiated by the server, if the client
+/// offers it and the application doesn't accept extensions on its own.
+#[derive(Clone, Debug)]
+pub struct DeflateConfig {
+    server_max_window_bits: u8,
+    client_max_window_bits: u8,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self {
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_max_window_bits: MAX_WINDOW_BITS,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+        }
+    }
+}
+
+impl DeflateConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets base-2 logarithm of LZ77 window size for messages, compressed by the server.
+    /// Clamped to `9..=15`, defaults to 15.
+    pub fn with_server_max_window_bits(mut self, bits: u8) -> Self {
+        self.server_max_window_bits = bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS);
+        self
+    }
+
+    /// Sets base-2 logarithm of LZ77 window size for messages, compressed by the client. Only
+    /// applies to clients, that offer to limit it. Clamped to `9..=15`, defaults to 15.
+    pub fn with_client_max_window_bits(mut self, bits: u8) -> Self {
+        self.client_max_window_bits = bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS);
+        self
+    }
+
+    /// Makes the server reset compression context after each message. It saves memory between
+    /// messages at the expense of compression ratio.
+    pub fn with_server_no_context_takeover(mut self, enabled: bool) -> Self {
+        self.server_no_context_takeover = enabled;
+        self
+    }
+
+    /// Requires the client to reset compression context after each message.
+    pub fn with_client_no_context_takeover(mut self, enabled: bool) -> Self {
+        self.client_no_context_takeover = enabled;
+        self
+    }
+
+    /// Returns parameters for the first acceptable offer of the client.
+    pub(crate) fn negotiate(&self, offers: &[WebSocketExtension]) -> Option<DeflateParams> {
+        offers
+            .iter()
+            .filter(|offer| offer.name == PERMESSAGE_DEFLATE)
+            .filter_map(DeflateParams::parse)
+            .find_map(|offer| {
+                let server_bits = offer
+                    .server_max_window_bits
+                    .unwrap_or(MAX_WINDOW_BITS)
+                    .min(self.server_max_window_bits);
+                if server_bits < MIN_WINDOW_BITS {
+                    return None;
+                }
+
+                let client_bits = offer.client_max_window_bits.map(|bits| {
+                    let bits = bits.unwrap_or(MAX_WINDOW_BITS);
+                    Some(bits.min(self.client_max_window_bits))
+                });
+
+                Some(DeflateParams {
+                    server_no_context_takeover: offer.server_no_context_takeover
+                        || self.server_no_context_takeover,
+                    client_no_context_takeover: offer.client_no_context_takeover
+                        || self.client_no_context_takeover,
+                    server_max_window_bits: (server_bits < MAX_WINDOW_BITS
+                        || offer.server_max_window_bits.is_some())
+                    .then_some(server_bits),
+                    client_max_window_bits: client_bits,
+                })
+            })
+    }
+}
+
+/// Parameters of `permessage-deflate` extension.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DeflateParams {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    server_max_window_bits: Option<u8>,
+    /// Clients may offer this parameter without a value.
+    client_max_window_bits: Option<Option<u8>>,
+}
+
+impl DeflateParams {
+    /// Parses parameters of the extension. Returns `None`, if they're unknown, duplicated or have
+    /// invalid values.
+    fn parse(extension: &WebSocketExtension) -> Option<Self> {
+        fn window_bits(value: Option<&str>) -> Option<u8> {
+            let value = value?.trim_matches('"');
+            if value.starts_with('0') {
+                return None;
+            }
+            let bits = value.parse().ok()?;
+            (8..=MAX_WINDOW_BITS).contains(&bits).then_some(bits)
+        }
+
+        let mut params = Self::default();
+        for (name, value) in &extension.params {
+            let value = value.as_deref();
+            match name.as_ref() {
+                "server_no_context_takeover"
+                    if value.is_none() && !params.server_no_context_takeover =>
+                {
+                    params.server_no_context_takeover = true;
+                }
+                "client_no_context_takeover"
+                    if value.is_none() && !params.client_no_context_takeover =>
+                {
+                    params.client_no_context_takeover = true;
+                }
+                "server_max_window_bits" if params.server_max_window_bits.is_none() => {
+                    params.server_max_window_bits = Some(window_bits(value)?);
+                }
+                "client_max_window_bits" if params.client_max_window_bits.is_none() => {
+                    let bits = match value {
+                        Some(_) => Some(window_bits(value)?),
+                        None => None,
+                    };
+                    params.client_max_window_bits = Some(bits);
+                }
+                _ => return None,
+            }
+        }
+        Some(params)
+    }
+
+    /// Parses parameters, accepted by the application. Returns `None`, if the server can't use
+    /// them.
+    pub(crate) fn from_response(extension: &WebSocketExtension) -> Option<Self> {
+        let params = Self::parse(extension)?;
+        let valid_server_bits = params
+            .server_max_window_bits
+            .map_or(true, |bits| bits >= MIN_WINDOW_BITS);
+        let valid_client_bits = !matches!(params.client_max_window_bits, Some(None));
+        (valid_server_bits && valid_client_bits).then_some(params)
+    }
+
+    pub(crate) fn to_extension(self) -> WebSocketExtension {
+        let mut extension = WebSocketExtension::default();
+        extension.name = PERMESSAGE_DEFLATE.into();
+        let params = &mut extension.params;
+        if self.server_no_context_takeover {
+            params.push(("server_no_context_takeover".into(), None));
+        }
+        if self.client_no_context_takeover {
+            params.push(("client_no_context_takeover".into(), None));
+        }
+        if let Some(bits) = self.server_max_window_bits {
+            params.push((
+                "server_max_window_bits".into(),
+                Some(bits.to_string().into()),
+            ));
+        }
+        if let Some(bits) = self.client_max_window_bits {
+            let value = bits.map(|bits| Cow::from(bits.to_string()));
+            params.push(("client_max_window_bits".into(), value));
+        }
+        extension
+    }
+}
+
+/// Frame rewriter, that compresses data frames of the server and decompresses data frames of the
+/// client.
+pub(crate) struct Deflate {
+    params: DeflateParams,
+    max_message_size: usize,
+    /// Size of the message, that is being read, after decompression.
+    read_size: usize,
+    compress: Compress,
+    decompress: Decompress,
+    /// Whether the message, that is being read, is compressed.
+    read_compressed: bool,
+    /// Whether the message, that is being written, is compressed.
+    write_compressed: bool,
+}
+
+impl Deflate {
+    pub(crate) fn new(params: DeflateParams, max_message_size: usize) -> Self {
+        let window_bits = params.server_max_window_bits.unwrap_or(MAX_WINDOW_BITS);
+        Self {
+            params,
+            max_message_size,
+            read_size: 0,
+            compress: Compress::new_with_window_bits(Compression::default(), false, window_bits),
+            // Maximum window can inflate data, compressed with any window size
+            decompress: Decompress::new_with_window_bits(false, MAX_WINDOW_BITS),
+            read_compressed: false,
+            write_compressed: false,
+        }
+    }
+}
+
+impl Rewrite for Deflate {
+    fn read_frame(
+        &mut self,
+        header: &FrameHeader,
+        mut frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()> {
+        let compressed = match header.opcode() {
+            OPCODE_TEXT | OPCODE_BINARY => {
+                self.read_compressed = header.is_compressed();
+                self.read_compressed
+            }
+            OPCODE_CONTINUATION => self.read_compressed && !header.is_compressed(),
+            _ => false,
+        };
+        if !compressed {
+            output.extend_from_slice(&frame);
+            return Ok(());
+        }
+
+        let payload = &mut frame[header.header_len..];
+        if let Some(mask) = header.mask {
+            unmask(payload, mask);
+        }
+
+        let limit = self
+            .max_message_size
+            .checked_sub(self.read_size)
+            .ok_or_else(|| close_error(1009, "message is too large"))?;
+        let mut data = Vec::new();
+        inflate(&mut self.decompress, payload, &mut data, limit)?;
+        if header.is_fin() {
+            inflate(&mut self.decompress, &TAIL, &mut data, limit)?;
+            self.read_compressed = false;
+            self.read_size = 0;
+            if self.params.client_no_context_takeover {
+                self.decompress.reset(false);
+            }
+        } else {
+            self.read_size += data.len();
+        }
+
+        let masked = header.mask.is_some();
+        put_header(output, header.head & !RSV1, masked, data.len());
+        output.extend_from_slice(&data);
+        Ok(())
+    }
+
+    fn write_frame(
+        &mut self,
+        header: &FrameHeader,
+        frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()> {
+        let head = match header.opcode() {
+            OPCODE_TEXT | OPCODE_BINARY => {
+                self.write_compressed = true;
+                header.head | RSV1
+            }
+            OPCODE_CONTINUATION if self.write_compressed => header.head,
+            _ => {
+                output.extend_from_slice(&frame);
+                return Ok(());
+            }
+        };
+
+        let mut data = Vec::new();
+        deflate(&mut self.compress, &frame[header.header_len..], &mut data)?;
+        if header.is_fin() {
+            if data.ends_with(&TAIL) {
+                data.truncate(data.len() - TAIL.len());
+            }
+            self.write_compressed = false;
+            if self.params.server_no_context_takeover {
+                self.compress.reset();
+            }
+        }
+
+        put_header(output, head, false, data.len());
+        output.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+/// Decompresses `input`, failing if the output exceeds `limit`.
+fn inflate(
+    decompress: &mut Decompress,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+    limit: usize,
+) -> io::Result<()> {
+    loop {
+        // Output may exceed the limit by one byte at most, to detect the overflow
+        let Some(room) = limit.checked_sub(output.len()) else {
+            return Err(close_error(1009, "message is too large"));
+        };
+        output.reserve_exact((input.len() * 2).max(1024).min(room.saturating_add(1)));
+        let total_in = decompress.total_in();
+        let total_out = decompress.total_out();
+        decompress
+            .decompress_vec(input, output, FlushDecompress::Sync)
+            .map_err(|_| close_error(1007, "invalid compressed data"))?;
+        if output.len() > limit {
+            return Err(close_error(1009, "message is too large"));
+        }
+        let consumed = (decompress.total_in() - total_in) as usize;
+        input = &input[consumed..];
+
+        if input.is_empty() && output.len() < output.capacity() {
+            return Ok(());
+        }
+        if consumed == 0 && decompress.total_out() == total_out {
+            return Err(close_error(1007, "invalid compressed data"));
+        }
+    }
+}
+
+fn deflate(compress: &mut Compress, mut input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        output.reserve(input.len() / 2 + 64);
+        let total_in = compress.total_in();
+        compress
+            .compress_vec(input, output, FlushCompress::Sync)
+            .map_err(|_| close_error(1011, "compression failed"))?;
+        let consumed = (compress.total_in() - total_in) as usize;
+        input = &input[consumed..];
+
+        if input.is_empty() && output.len() < output.capacity() {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{BoxBody, BoxError};
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use hyper::body::{Body, Frame};
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Error, that occurred while building HTTP response from Servio service.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ResponseError<E> {
+    /// Service returned an error.
+    Service(E),
+    /// App stream ended before `ResponseStart` was sent.
+    UnexpectedEnd,
+    /// App stream sent an event other than `ResponseStart` to start a response.
+    UnexpectedEvent(Cow<'static, str>),
+}
+
+impl<E: fmt::Display> fmt::Display for ResponseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Service(e) => write!(f, "service error: {e}"),
+            Self::UnexpectedEnd => f.write_str("app stream ended before ResponseStart"),
+            Self::UnexpectedEvent(event) => write!(f, "expected ResponseStart, got {event}"),
+        }
+    }
+}
+
+impl<E: StdError + 'static> StdError for ResponseError<E> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Service(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+type FallbackFn = dyn Fn(&dyn StdError) -> Option<Response<Bytes>> + Send + Sync;
+
+/// Strategy of handling a [`ResponseError`], that occurred before the response has started.
+///
+/// Errors after the response has started always abort the response body, as status and headers
+/// are already sent.
+#[derive(Clone, Default)]
+pub enum ErrorFallback {
+    /// Respond with empty `500 Internal Server Error`.
+    #[default]
+    InternalServerError,
+    /// Return the error to `hyper`, that aborts the connection.
+    Abort,
+    /// Build a response with a callback. If it returns `None`, the connection is aborted.
+    Custom(Arc<FallbackFn>),
+}
+
+impl ErrorFallback {
+    /// Creates [`ErrorFallback::Custom`] from a callback.
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&dyn StdError) -> Option<Response<Bytes>> + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(f))
+    }
+
+    pub(crate) fn handle<E: StdError + 'static>(
+        &self,
+        result: Result<Response<BoxBody>, ResponseError<E>>,
+    ) -> Result<Response<BoxBody>, ResponseError<E>> {
+        let Err(e) = result else {
+            return result;
+        };
+
+        let response = match self {
+            Self::InternalServerError => {
+                let mut response = Response::new(Bytes::new());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                Some(response)
+            }
+            Self::Abort => None,
+            Self::Custom(f) => f(&e),
+        };
+
+        match response {
+            Some(response) => Ok(response.map(|body| Box::pin(FullBody::new(body)) as BoxBody)),
+            None => Err(e),
+        }
+    }
+}
+
+impl fmt::Debug for ErrorFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InternalServerError => f.write_str("InternalServerError"),
+            Self::Abort => f.write_str("Abort"),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Body, consisting of a single data frame.
+pub(crate) struct FullBody {
+    data: Option<Bytes>,
+}
+
+impl FullBody {
+    pub(crate) fn new(data: Bytes) -> Self {
+        Self {
+            data: Some(data).filter(|data| !data.is_empty()),
+        }
+    }
+}
+
+impl Body for FullBody {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Poll::Ready(self.data.take().map(|data| Ok(Frame::data(data))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_none()
+    }
+}
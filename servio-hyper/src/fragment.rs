@@ -0,0 +1,89 @@
+//! Delivery of client messages frame by frame.
+//!
+//! `tungstenite` reads whole messages, so every data frame of the client is rewritten into
+//! a separate binary message. Its payload starts with a byte of [`TEXT`] and [`MORE`] flags.
+
+use crate::frame::{
+    close_error, put_header, unmask, FrameHeader, Rewrite, FIN, OPCODE_BINARY, OPCODE_CONTINUATION,
+    OPCODE_TEXT,
+};
+use bytes::{BufMut, BytesMut};
+use std::io;
+
+/// The frame belongs to a text message.
+pub(crate) const TEXT: u8 = 0x1;
+/// The message continues in the next frame.
+pub(crate) const MORE: u8 = 0x2;
+
+/// Frame rewriter, that splits client messages into frames.
+pub(crate) struct Fragments {
+    max_message_size: usize,
+    /// Whether the message, that is being read, is text, and its size so far.
+    message: Option<(bool, usize)>,
+}
+
+impl Fragments {
+    pub(crate) fn new(max_message_size: usize) -> Self {
+        Self {
+            max_message_size,
+            message: None,
+        }
+    }
+}
+
+impl Rewrite for Fragments {
+    fn read_frame(
+        &mut self,
+        header: &FrameHeader,
+        mut frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()> {
+        let (text, size) = match (header.opcode(), self.message) {
+            (OPCODE_TEXT | OPCODE_BINARY, None) => (header.opcode() == OPCODE_TEXT, 0),
+            (OPCODE_CONTINUATION, Some(message)) => message,
+            (OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION, _) => {
+                return Err(close_error(1002, "unexpected data frame"));
+            }
+            // Control frames are handled by `tungstenite`
+            _ => {
+                output.extend_from_slice(&frame);
+                return Ok(());
+            }
+        };
+
+        let size = size + header.payload_len;
+        if size > self.max_message_size {
+            return Err(close_error(1009, "message is too large"));
+        }
+        self.message = (!header.is_fin()).then_some((text, size));
+
+        let payload = &mut frame[header.header_len..];
+        if let Some(mask) = header.mask {
+            unmask(payload, mask);
+        }
+
+        let mut flags = 0;
+        if text {
+            flags |= TEXT;
+        }
+        if !header.is_fin() {
+            flags |= MORE;
+        }
+        // Reserved bits are kept for `tungstenite` to reject
+        let head = FIN | (header.head & 0x70) | OPCODE_BINARY;
+        put_header(output, head, header.mask.is_some(), payload.len() + 1);
+        output.put_u8(flags);
+        output.extend_from_slice(payload);
+        Ok(())
+    }
+
+    fn write_frame(
+        &mut self,
+        _header: &FrameHeader,
+        frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()> {
+        output.extend_from_slice(&frame);
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+//! Raw WebSocket frames, that are rewritten by extensions below `tungstenite`.
+
+use bytes::{Buf, BufMut, BytesMut};
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pub(crate) const OPCODE_CONTINUATION: u8 = 0x0;
+pub(crate) const OPCODE_TEXT: u8 = 0x1;
+pub(crate) const OPCODE_BINARY: u8 = 0x2;
+
+pub(crate) const FIN: u8 = 0x80;
+pub(crate) const RSV1: u8 = 0x40;
+
+/// Header of a complete frame in the buffer.
+pub(crate) struct FrameHeader {
+    /// First byte of the frame with FIN, RSV and opcode bits.
+    pub(crate) head: u8,
+    pub(crate) mask: Option<[u8; 4]>,
+    pub(crate) header_len: usize,
+    pub(crate) payload_len: usize,
+}
+
+impl FrameHeader {
+    /// Parses header of the first frame in the buffer. Returns `None`, if the frame is incomplete.
+    /// Fails, if payload is larger than `max_payload_len`.
+    pub(crate) fn parse(buf: &[u8], max_payload_len: Option<usize>) -> io::Result<Option<Self>> {
+        let [head, second, ..] = *buf else {
+            return Ok(None);
+        };
+
+        let (payload_len, mut header_len) = match second & 0x7f {
+            126 => match buf.get(2..4) {
+                Some(len) => (u64::from(u16::from_be_bytes([len[0], len[1]])), 4),
+                None => return Ok(None),
+            },
+            127 => match buf.get(2..10) {
+                Some(len) => (u64::from_be_bytes(len.try_into().unwrap()), 10),
+                None => return Ok(None),
+            },
+            len => (u64::from(len), 2),
+        };
+        let payload_len = usize::try_from(payload_len)
+            .ok()
+            .filter(|&len| max_payload_len.map_or(true, |max| len <= max))
+            .ok_or_else(|| close_error(1009, "frame is too large"))?;
+
+        let mut mask = None;
+        if second & 0x80 != 0 {
+            let Some(key) = buf.get(header_len..header_len + 4) else {
+                return Ok(None);
+            };
+            mask = Some(key.try_into().unwrap());
+            header_len += 4;
+        }
+
+        if buf.len() - header_len < payload_len {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            head,
+            mask,
+            header_len,
+            payload_len,
+        }))
+    }
+
+    pub(crate) fn opcode(&self) -> u8 {
+        self.head & 0x0f
+    }
+
+    pub(crate) fn is_fin(&self) -> bool {
+        self.head & FIN != 0
+    }
+
+    pub(crate) fn is_compressed(&self) -> bool {
+        self.head & RSV1 != 0
+    }
+}
+
+/// Unmasks payload of a frame, received from the client.
+pub(crate) fn unmask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// Writes frame header. Masked frames get zero masking key, so the payload stays as is.
+pub(crate) fn put_header(buf: &mut BytesMut, head: u8, masked: bool, payload_len: usize) {
+    let mask_bit = if masked { 0x80 } else { 0 };
+    buf.put_u8(head);
+    if payload_len < 126 {
+        buf.put_u8(mask_bit | payload_len as u8);
+    } else if let Ok(len) = u16::try_from(payload_len) {
+        buf.put_u8(mask_bit | 126);
+        buf.put_u16(len);
+    } else {
+        buf.put_u8(mask_bit | 127);
+        buf.put_u64(payload_len as u64);
+    }
+    if masked {
+        buf.put_slice(&[0; 4]);
+    }
+}
+
+/// Error, that fails the connection with the close code.
+#[derive(Debug)]
+pub(crate) struct CloseError {
+    pub(crate) code: u16,
+    reason: &'static str,
+}
+
+impl fmt::Display for CloseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reason)
+    }
+}
+
+impl std::error::Error for CloseError {}
+
+pub(crate) fn close_error(code: u16, reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, CloseError { code, reason })
+}
+
+/// Rewrites frames, that pass through a [`FrameStream`].
+pub(crate) trait Rewrite {
+    /// Rewrites a frame, received from the client, into `output`.
+    fn read_frame(
+        &mut self,
+        header: &FrameHeader,
+        frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()>;
+
+    /// Rewrites a frame, written by `tungstenite`, into `output`.
+    fn write_frame(
+        &mut self,
+        header: &FrameHeader,
+        frame: BytesMut,
+        output: &mut BytesMut,
+    ) -> io::Result<()>;
+}
+
+/// Server side connection, that rewrites complete frames in both directions.
+pub(crate) struct FrameStream<S, R> {
+    inner: S,
+    rewrite: R,
+    /// Maximum payload size of frames, received from the client.
+    max_frame_size: Option<usize>,
+    /// Bytes, received from the client, that don't make a complete frame yet.
+    read_raw: BytesMut,
+    /// Rewritten frames, that are ready to be read.
+    read_ready: BytesMut,
+    read_eof: bool,
+    /// Bytes, written by `tungstenite`, that don't make a complete frame yet.
+    write_raw: BytesMut,
+    /// Rewritten frames, that are ready to be sent.
+    write_ready: BytesMut,
+}
+
+impl<S, R: Rewrite> FrameStream<S, R> {
+    pub(crate) fn new(inner: S, rewrite: R, max_frame_size: Option<usize>) -> Self {
+        Self {
+            inner,
+            rewrite,
+            max_frame_size,
+            read_raw: BytesMut::new(),
+            read_ready: BytesMut::new(),
+            read_eof: false,
+            write_raw: BytesMut::new(),
+            write_ready: BytesMut::new(),
+        }
+    }
+
+    fn process_read(&mut self) -> io::Result<()> {
+        while let Some(header) = FrameHeader::parse(&self.read_raw, self.max_frame_size)? {
+            let frame = self
+                .read_raw
+                .split_to(header.header_len + header.payload_len);
+            self.rewrite
+                .read_frame(&header, frame, &mut self.read_ready)?;
+        }
+        Ok(())
+    }
+
+    fn process_write(&mut self) -> io::Result<()> {
+        while let Some(header) = FrameHeader::parse(&self.write_raw, None)? {
+            let frame = self
+                .write_raw
+                .split_to(header.header_len + header.payload_len);
+            self.rewrite
+                .write_frame(&header, frame, &mut self.write_ready)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: AsyncWrite + Unpin, R> FrameStream<S, R> {
+    /// Sends rewritten frames to the client.
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_ready.is_empty() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.write_ready))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_ready.advance(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin, R: Rewrite + Unpin> AsyncRead for FrameStream<S, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if !this.read_ready.is_empty() {
+                let len = this.read_ready.len().min(buf.remaining());
+                buf.put_slice(&this.read_ready.split_to(len));
+                return Poll::Ready(Ok(()));
+            }
+            if this.read_eof {
+                // Incomplete frame is left for `tungstenite` to report
+                let rest = this.read_raw.split();
+                this.read_ready.extend_from_slice(&rest);
+                if this.read_ready.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+
+            let mut chunk = [0; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                this.read_eof = true;
+            }
+            this.read_raw.extend_from_slice(chunk.filled());
+            this.process_read()?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin, R: Rewrite + Unpin> AsyncWrite for FrameStream<S, R> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_ready(cx))?;
+        this.write_raw.extend_from_slice(buf);
+        this.process_write()?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_ready(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_ready(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
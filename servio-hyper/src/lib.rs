@@ -1,8 +1,34 @@
 #![forbid(unsafe_code)]
+mod conn;
+#[cfg(feature = "websocket")]
+mod deflate;
+mod error;
+#[cfg(feature = "websocket")]
+mod fragment;
+#[cfg(feature = "websocket")]
+mod frame;
+mod lifespan;
+mod listener;
+mod server;
+#[cfg(feature = "tls")]
+mod tls;
 #[cfg(feature = "websocket")]
 mod websocket;
 
+pub use conn::{serve_connection, serve_connection_with_shutdown, HttpProtocol, TokioExecutor};
+#[cfg(feature = "websocket")]
+pub use deflate::DeflateConfig;
+pub use error::{ErrorFallback, ResponseError};
+pub use lifespan::{Lifespan, LifespanError, LifespanServerStream};
+pub use server::{Server, ShutdownSignal};
+#[cfg(feature = "tls")]
+pub use tls::{ClientAuth, TlsConfig, TlsError};
+#[cfg(feature = "websocket")]
+pub use websocket::{Servio2HyperWebSocket, WebSocketConfig, WebSocketServerStream};
+
+use crate::server::TaskSender;
 use bytes::Bytes;
+use futures_channel::oneshot;
 use futures_core::future::BoxFuture;
 use futures_core::stream::Stream;
 use futures_util::{FutureExt, StreamExt, TryFutureExt};
@@ -10,81 +36,235 @@ use http::{Request, Response};
 use hyper::body::{Body, Frame, Incoming as IncomingBody};
 use hyper::service::Service as HyperService;
 use servio_http::http::{
-    HttpEvent, HttpScope, RequestChunk, ResponseChunk, ResponseStart, ResponseTrailer, EVENT_HTTP,
-    PROTOCOL_HTTP,
+    Address, Disconnect, HttpEvent, HttpFamily, HttpScope, RequestChunk, RequestTrailer,
+    ResponseChunk, ResponseStart, ResponseTrailer, PROTOCOL_HTTP,
 };
-use servio_service::{Event, Scope, Service};
+use servio_http::tls::TlsScope;
+use servio_service::{Event, MakeService, ReadyService, Scope, Service};
+use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::future::Future;
-use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{ready, Context, Poll};
 
 /// Servio to `hyper` service wrapper. It can be used to transform Servio-compatible service into
 /// `hyper`-compatible one.
+///
+/// Errors, that occur before the response has started, are handled by [`ErrorFallback`], which
+/// responds with `500 Internal Server Error` by default.
 pub struct Servio2Hyper<T> {
     inner: T,
-    server: Option<SocketAddr>,
-    client: Option<SocketAddr>,
+    server: Option<Address>,
+    client: Option<Address>,
+    fallback: ErrorFallback,
+    shutdown: Option<ShutdownSignal>,
+    tls: Option<TlsScope>,
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    tasks: Option<TaskSender>,
 }
 
 type BoxError = Box<dyn StdError + Send + Sync>;
 type BoxBody = Pin<Box<dyn Body<Error = BoxError, Data = Bytes> + Send>>;
 
-impl<T> Servio2Hyper<T> {
-    pub fn new(service: T, server: Option<SocketAddr>, client: Option<SocketAddr>) -> Self {
+/// Connection-level information, that is passed to a [`MakeService`] to create a service for
+/// the connection.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionInfo {
+    pub server: Option<Address>,
+    pub client: Option<Address>,
+    /// TLS details, if the connection is encrypted. [`Server`] makes services after TLS
+    /// handshake, so they can be parameterized with it.
+    pub tls: Option<TlsScope>,
+}
+
+impl ConnectionInfo {
+    pub fn new(server: Option<Address>, client: Option<Address>) -> Self {
         Self {
-            inner: service,
             server,
             client,
+            tls: None,
         }
     }
 }
 
 impl<T> Servio2Hyper<T> {
-    async fn build_response<AS, E>(mut app_stream: AS) -> Result<Response<BoxBody>, E>
+    pub fn new(service: T, server: Option<Address>, client: Option<Address>) -> Self {
+        Self {
+            inner: service,
+            server,
+            client,
+            fallback: ErrorFallback::default(),
+            shutdown: None,
+            tls: None,
+            tasks: None,
+        }
+    }
+
+    /// Sets the strategy of handling errors, that occur before the response has started.
+    pub fn with_fallback(mut self, fallback: ErrorFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Sets the signal of server shutdown, that is passed to server streams.
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Marks the connection as encrypted. [`TlsScope`] is inserted into request scopes, and
+    /// `scheme` of `HttpScope` is set to `https`.
+    pub fn with_tls(mut self, tls: TlsScope) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Creates a service for the connection using service factory and wraps it. If `info` has
+    /// TLS details, the connection is marked as encrypted.
+    pub async fn make<M>(make_service: &mut M, info: ConnectionInfo) -> Result<Self, M::MakeError>
     where
-        AS: Stream<Item = Event> + Send + Unpin + 'static,
+        M: MakeService<ConnectionInfo, BodyServerStream, Service = T>,
     {
-        let Some(event) = app_stream.next().await else {
-            panic!("Unexpected EOF from application");
-        };
+        let service = make_service.make_service(info.clone()).await?;
+        Ok(Self::from_info(service, info))
+    }
 
-        let Some(event) = event.get::<HttpEvent>() else {
-            panic!("Cannot get message from scope");
-        };
+    /// Passes tasks of upgraded connections to the server, so that its shutdown covers them.
+    pub(crate) fn with_tasks(mut self, tasks: TaskSender) -> Self {
+        self.tasks = Some(tasks);
+        self
+    }
 
-        match event.as_ref() {
-            HttpEvent::ResponseStart(ResponseStart {
-                status,
-                headers,
-                trailers,
-                ..
-            }) => {
-                let wrapped_body = BodyAppStream::new(app_stream, *trailers);
-                let body: BoxBody = Box::pin(wrapped_body);
-
-                let response = {
-                    let mut builder = Response::builder().status(status);
-                    *builder.headers_mut().unwrap() = headers.clone();
-                    builder.body(body).unwrap()
-                };
-
-                Ok(response)
-            }
-            _ => panic!("Unexpected message type"),
+    pub(crate) fn from_info(service: T, info: ConnectionInfo) -> Self {
+        let ConnectionInfo {
+            server,
+            client,
+            tls,
+        } = info;
+        let mut wrapper = Self::new(service, server, client);
+        wrapper.tls = tls;
+        wrapper
+    }
+}
+
+impl<T> Servio2Hyper<T> {
+    /// Converts hyper request into Servio scope and server stream. Returned guard must be held
+    /// by the response.
+    fn prepare(&self, req: Request<IncomingBody>) -> (Scope, BodyServerStream, ResponseGuard) {
+        let (parts, body) = req.into_parts();
+
+        let mut http_scope = make_http_scope(
+            parts.method,
+            parts.uri,
+            parts.version,
+            parts.headers,
+            self.server.clone(),
+            self.client.clone(),
+        );
+
+        let mut scope = Scope::new(PROTOCOL_HTTP.into());
+        if let Some(tls) = &self.tls {
+            http_scope.scheme = "https".into();
+            scope = scope.with_scope(tls.clone());
+        }
+        let scope = scope.with_scope(http_scope);
+
+        let (guard, disconnect) = oneshot::channel();
+        let mut server_stream = BodyServerStream::new(body);
+        server_stream.disconnect = Some(disconnect);
+        if let Some(shutdown) = &self.shutdown {
+            server_stream = server_stream.with_shutdown(shutdown.clone());
         }
+
+        (scope, server_stream, ResponseGuard { _tx: guard })
+    }
+}
+
+/// Builds response from `ResponseStart` event and body events, that follow it.
+pub(crate) async fn build_response<AS, E>(
+    mut app_stream: AS,
+    guard: Option<ResponseGuard>,
+) -> Result<Response<BoxBody>, ResponseError<E>>
+where
+    AS: Stream<Item = Event> + Send + Unpin + 'static,
+{
+    let Some(event) = app_stream.next().await else {
+        return Err(ResponseError::UnexpectedEnd);
+    };
+
+    let Some(event) = event.downcast::<HttpFamily>() else {
+        let family = event.family().to_owned();
+        return Err(ResponseError::UnexpectedEvent(family.into()));
+    };
+
+    match event {
+        HttpEvent::ResponseStart(ResponseStart {
+            status,
+            headers,
+            trailers,
+            ..
+        }) => {
+            let mut wrapped_body = BodyAppStream::new(app_stream, *trailers);
+            wrapped_body.guard = guard;
+            let body: BoxBody = Box::pin(wrapped_body);
+
+            let response = {
+                let mut builder = Response::builder().status(status);
+                *builder.headers_mut().unwrap() = headers.clone();
+                builder.body(body).unwrap()
+            };
+
+            Ok(response)
+        }
+        event => Err(ResponseError::UnexpectedEvent(format!("{event:?}").into())),
     }
 }
 
+/// Guard, that is held by the response. Server stream sends `Disconnect`, when it is dropped.
+struct ResponseGuard {
+    _tx: oneshot::Sender<()>,
+}
+
+/// Server stream, that sends request body as `RequestChunk` and `RequestTrailer` events.
+///
+/// When created by [`Servio2Hyper`], the stream stays open after the request body and sends
+/// `Disconnect` event, when the response is complete or the client has gone away. Standalone
+/// stream ends after the request body.
+///
+/// If server shutdown is signalled, the request body is still sent in full, and then `Disconnect`
+/// event is sent without waiting for the response.
 pub struct BodyServerStream {
     body: IncomingBody,
+    body_end: bool,
     end: bool,
+    disconnect: Option<oneshot::Receiver<()>>,
+    shutdown: Option<ShutdownSignal>,
 }
 
 impl BodyServerStream {
     pub fn new(body: IncomingBody) -> Self {
-        Self { body, end: false }
+        Self {
+            body,
+            body_end: false,
+            end: false,
+            disconnect: None,
+            shutdown: None,
+        }
+    }
+
+    /// Sets the signal of server shutdown.
+    pub fn with_shutdown(mut self, shutdown: ShutdownSignal) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+}
+
+impl BodyServerStream {
+    fn disconnect(&mut self) -> Poll<Option<Event>> {
+        self.end = true;
+        let http_event = HttpEvent::Disconnect(Disconnect::default());
+        Poll::Ready(Some(Event::typed::<HttpFamily>(http_event)))
     }
 }
 
@@ -96,25 +276,53 @@ impl Stream for BodyServerStream {
             return Poll::Ready(None);
         }
 
-        let frame = ready!(Pin::new(&mut self.body).poll_frame(cx));
+        if !self.body_end {
+            let frame = ready!(Pin::new(&mut self.body).poll_frame(cx));
 
-        let http_event = match frame {
+            let http_event = match frame {
+                None => {
+                    self.body_end = true;
+                    HttpEvent::RequestChunk(RequestChunk::default())
+                }
+                Some(Ok(frame)) if frame.is_data() => HttpEvent::RequestChunk({
+                    let mut event = RequestChunk::default();
+                    event.body = frame.into_data().unwrap_or_default();
+                    event.more = !self.body.is_end_stream();
+                    self.body_end = !event.more;
+                    event
+                }),
+                // Trailers are always the last frame of a body
+                Some(Ok(frame)) => HttpEvent::RequestTrailer({
+                    let mut event = RequestTrailer::default();
+                    event.headers = frame.into_trailers().unwrap_or_default();
+                    self.body_end = true;
+                    event
+                }),
+                Some(Err(_)) => return self.disconnect(),
+            };
+
+            return Poll::Ready(Some(Event::typed::<HttpFamily>(http_event)));
+        }
+
+        // Body has ended, wait until response is complete or dropped, or server shuts down
+        let shutdown = match self.shutdown.as_mut() {
+            Some(shutdown) => Pin::new(shutdown).poll(cx).is_ready(),
+            None => false,
+        };
+        if shutdown {
+            return self.disconnect();
+        }
+
+        match self.disconnect.as_mut() {
+            Some(disconnect) => {
+                let _ = ready!(disconnect.poll_unpin(cx));
+                self.disconnect()
+            }
             None => {
                 self.end = true;
-                HttpEvent::RequestChunk(RequestChunk::default())
+                Poll::Ready(None)
             }
-            Some(Ok(frame)) => HttpEvent::RequestChunk({
-                let mut event = RequestChunk::default();
-                event.body = frame
-                    .into_data()
-                    .expect("only data is available in request");
-                event.more = !self.body.is_end_stream();
-                event
-            }),
-            Some(Err(e)) => panic!("{e}"),
-        };
-
-        Poll::Ready(Some(Event::new(EVENT_HTTP.into(), http_event)))
+        }
     }
 }
 
@@ -123,6 +331,7 @@ struct BodyAppStream<S> {
     has_trailers: bool,
     body_end: bool,
     trailers_end: bool,
+    guard: Option<ResponseGuard>,
 }
 
 impl<S> BodyAppStream<S> {
@@ -132,6 +341,7 @@ impl<S> BodyAppStream<S> {
             has_trailers,
             body_end: false,
             trailers_end: false,
+            guard: None,
         }
     }
 }
@@ -152,9 +362,8 @@ where
                 return Poll::Ready(None);
             };
 
-            if event.family() == EVENT_HTTP {
-                let event = event.get::<HttpEvent>().unwrap();
-                match event.as_ref() {
+            if let Some(event) = event.downcast::<HttpFamily>() {
+                match event {
                     HttpEvent::ResponseChunk(ResponseChunk { body, more, .. }) => {
                         self.body_end = !*more;
                         let frame = Frame::data(body.clone());
@@ -165,7 +374,12 @@ where
                         let frame = Frame::trailers(headers.clone());
                         return Poll::Ready(Some(Ok(frame)));
                     }
-                    _ => panic!("Unexpected event: {event:?}"),
+                    event => {
+                        let e = ResponseError::<Infallible>::UnexpectedEvent(
+                            format!("{event:?}").into(),
+                        );
+                        return Poll::Ready(Some(Err(e.into())));
+                    }
                 }
             }
         }
@@ -178,54 +392,99 @@ where
 
 impl<T, E, F, AS> HyperService<Request<IncomingBody>> for Servio2Hyper<T>
 where
-    E: StdError,
+    E: StdError + Send + 'static,
     AS: Stream<Item = Event> + Send + Unpin + 'static,
     F: Future<Output = Result<AS, E>> + Send + 'static,
     T: Service<BodyServerStream, Error = E, Future = F>,
 {
     type Response = Response<BoxBody>;
-    type Error = T::Error;
+    type Error = ResponseError<T::Error>;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     fn call(&mut self, req: Request<IncomingBody>) -> Self::Future {
-        // Prepare request
-        let (parts, body) = req.into_parts();
-
-        let http_scope = make_http_scope(
-            parts.method,
-            parts.uri,
-            parts.version,
-            parts.headers,
-            self.server,
-            self.client,
-        );
-
-        let scope = Scope::new(PROTOCOL_HTTP.into()).with_scope(http_scope);
-
-        let server_stream = BodyServerStream::new(body);
+        let (scope, server_stream, guard) = self.prepare(req);
+        let fallback = self.fallback.clone();
 
         // Fire scope and server stream into the wrapped service, get app stream in return
         let resp_fut = self
             .inner
             .call(scope, server_stream)
-            .and_then(|app_stream| async move { Self::build_response(app_stream).await });
+            .map_err(ResponseError::Service)
+            .and_then(|app_stream| async move { build_response(app_stream, Some(guard)).await })
+            .map(move |result| fallback.handle(result));
 
         resp_fut.boxed()
     }
 }
 
+/// Servio to `hyper` service wrapper, that honors readiness of [`ReadyService`].
+///
+/// Before dispatching a request, it waits until the service is ready, so a saturated service
+/// holds back the connection instead of accepting more work. The service is cloned for every
+/// request, so it must be cheap to clone.
+pub struct ReadyServio2Hyper<T> {
+    inner: Servio2Hyper<T>,
+}
+
+impl<T> ReadyServio2Hyper<T> {
+    pub fn new(service: T, server: Option<Address>, client: Option<Address>) -> Self {
+        Self {
+            inner: Servio2Hyper::new(service, server, client),
+        }
+    }
+}
+
+impl<T> From<Servio2Hyper<T>> for ReadyServio2Hyper<T> {
+    fn from(inner: Servio2Hyper<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T, E, F, AS> HyperService<Request<IncomingBody>> for ReadyServio2Hyper<T>
+where
+    E: StdError + Send + 'static,
+    AS: Stream<Item = Event> + Send + Unpin + 'static,
+    F: Future<Output = Result<AS, E>> + Send + 'static,
+    T: ReadyService<BodyServerStream, Error = E, Future = F> + Clone + Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = ResponseError<T::Error>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn call(&mut self, req: Request<IncomingBody>) -> Self::Future {
+        let (scope, server_stream, guard) = self.inner.prepare(req);
+        let mut service = self.inner.inner.clone();
+        let fallback = self.inner.fallback.clone();
+
+        async move {
+            let result = async {
+                servio_service::ready(&mut service)
+                    .await
+                    .map_err(ResponseError::Service)?;
+                let app_stream = service
+                    .call(scope, server_stream)
+                    .await
+                    .map_err(ResponseError::Service)?;
+                build_response(app_stream, Some(guard)).await
+            };
+            fallback.handle(result.await)
+        }
+        .boxed()
+    }
+}
+
 #[inline]
 pub(crate) fn make_http_scope(
     method: http::Method,
     uri: http::Uri,
     version: http::Version,
     headers: http::HeaderMap,
-    server: Option<SocketAddr>,
-    client: Option<SocketAddr>,
+    server: Option<Address>,
+    client: Option<Address>,
 ) -> HttpScope {
     let mut http_scope = HttpScope::default();
     http_scope.method = method;
-    http_scope.uri = uri;
+    http_scope.set_uri(uri);
     http_scope.version = version;
     http_scope.headers = headers;
     http_scope.server = server;
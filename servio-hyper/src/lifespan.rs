@@ -0,0 +1,122 @@
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use servio_http::lifespan::{
+    LifespanEvent, LifespanFamily, LifespanScope, Shutdown, ShutdownFailed, Startup, StartupFailed,
+    PROTOCOL_LIFESPAN,
+};
+use servio_service::{Event, Scope, Service};
+use std::borrow::Cow;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Error, returned by application in response to lifespan events.
+#[derive(Clone, Debug)]
+pub enum LifespanError {
+    StartupFailed(Cow<'static, str>),
+    ShutdownFailed(Cow<'static, str>),
+}
+
+impl fmt::Display for LifespanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LifespanError::StartupFailed(message) => write!(f, "startup failed: {message}"),
+            LifespanError::ShutdownFailed(message) => write!(f, "shutdown failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LifespanError {}
+
+/// Server stream of lifespan protocol.
+pub struct LifespanServerStream {
+    rx: UnboundedReceiver<Event>,
+}
+
+impl Stream for LifespanServerStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx)
+    }
+}
+
+/// Driver of lifespan protocol. Server should call [`Lifespan::startup`] before accepting
+/// connections and [`Lifespan::shutdown`] after it stops accepting them. [`Server`] does it for
+/// the service, that is set with [`Server::with_lifespan`].
+///
+/// [`Server`]: crate::Server
+/// [`Server::with_lifespan`]: crate::Server::with_lifespan
+///
+/// If application does not support lifespan protocol, both steps succeed without doing anything.
+pub struct Lifespan<AS> {
+    state: Option<(UnboundedSender<Event>, AS)>,
+}
+
+impl<AS> Lifespan<AS>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    /// Sends `Startup` event to application and waits for the response.
+    pub async fn startup<S>(service: &mut S) -> Result<Self, LifespanError>
+    where
+        S: Service<LifespanServerStream, AppStream = AS>,
+    {
+        let (tx, rx) = unbounded();
+        let scope = Scope::new(PROTOCOL_LIFESPAN.into()).with_scope(LifespanScope::default());
+
+        let event = LifespanEvent::Startup(Startup::default());
+        let _ = tx.unbounded_send(Event::typed::<LifespanFamily>(event));
+
+        let Ok(mut app_stream) = service.call(scope, LifespanServerStream { rx }).await else {
+            return Ok(Self { state: None });
+        };
+
+        match next_event(&mut app_stream).await {
+            Some(LifespanEvent::StartupComplete(..)) => Ok(Self {
+                state: Some((tx, app_stream)),
+            }),
+            Some(LifespanEvent::StartupFailed(StartupFailed { message, .. })) => {
+                Err(LifespanError::StartupFailed(message.clone()))
+            }
+            _ => Ok(Self { state: None }),
+        }
+    }
+
+    /// Returns `true` if application has completed startup using lifespan protocol.
+    pub fn is_supported(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Sends `Shutdown` event to application and waits for the response.
+    pub async fn shutdown(self) -> Result<(), LifespanError> {
+        let Some((tx, mut app_stream)) = self.state else {
+            return Ok(());
+        };
+
+        let event = LifespanEvent::Shutdown(Shutdown::default());
+        if tx
+            .unbounded_send(Event::typed::<LifespanFamily>(event))
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        match next_event(&mut app_stream).await {
+            Some(LifespanEvent::ShutdownFailed(ShutdownFailed { message, .. })) => {
+                Err(LifespanError::ShutdownFailed(message.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns next lifespan event. Other events are treated as an end of stream.
+async fn next_event<AS>(app_stream: &mut AS) -> Option<LifespanEvent>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    let event = app_stream.next().await?;
+    event.downcast::<LifespanFamily>().cloned()
+}
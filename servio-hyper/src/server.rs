@@ -0,0 +1,470 @@
+use crate::listener::Listener;
+#[cfg(feature = "tls")]
+use crate::TlsConfig;
+use crate::{serve_connection_with_shutdown, BodyServerStream, BoxError, ConnectionInfo};
+use crate::{ErrorFallback, HttpProtocol, Lifespan, LifespanServerStream, Servio2Hyper};
+use futures_channel::{mpsc, oneshot};
+use futures_core::future::BoxFuture;
+use futures_util::future::{self, FutureExt, Shared};
+use futures_util::StreamExt;
+use http::{Request, Response};
+use hyper::body::{Body, Incoming as IncomingBody};
+use hyper::service::Service as HyperService;
+use servio_http::http::Address;
+use servio_service::{BoxService, Identity, Layer, MakeService, Service};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::task::JoinSet;
+#[cfg(unix)]
+use {
+    std::fs::{self, Permissions},
+    std::os::unix::fs::{DirBuilderExt, FileTypeExt},
+    std::path::Path,
+    tokio::net::UnixListener,
+};
+
+/// Signal, that completes when server starts shutting down. It is cheap to clone.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal {
+    rx: Shared<oneshot::Receiver<()>>,
+}
+
+impl ShutdownSignal {
+    /// Creates a signal with its trigger. Signal completes when the trigger is used or dropped.
+    pub fn new() -> (oneshot::Sender<()>, Self) {
+        let (tx, rx) = oneshot::channel();
+        (tx, Self { rx: rx.shared() })
+    }
+}
+
+impl Future for ShutdownSignal {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.rx.poll_unpin(cx).map(|_| ())
+    }
+}
+
+/// Sender of tasks, that serve upgraded connections, to a [`Server`]. Tasks are spawned
+/// separately, if the server has stopped.
+#[derive(Clone)]
+pub(crate) struct TaskSender(mpsc::UnboundedSender<BoxFuture<'static, ()>>);
+
+impl TaskSender {
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub(crate) fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if let Err(e) = self.0.unbounded_send(task.boxed()) {
+            tokio::spawn(e.into_inner());
+        }
+    }
+}
+
+/// HTTP server, that accepts TCP or Unix domain socket connections and serves them with
+/// services, created by a [`MakeService`]. With `tls` feature, connections can be encrypted with
+/// [`Server::with_tls`].
+///
+/// Connections are served with [`Servio2Hyper`], that can be wrapped into another `hyper` service
+/// with [`Server::with_adapter`]. With `websocket` feature, WebSocket connections are served by
+/// passing [`WebSocketConfig`] as the adapter.
+///
+/// [`WebSocketConfig`]: crate::WebSocketConfig
+///
+/// If a lifespan service is set with [`Server::with_lifespan`], `Startup` is sent to it before
+/// the first connection is accepted, and `Shutdown` after all connections are closed.
+///
+/// On graceful shutdown, the server stops accepting connections and waits for in-flight
+/// requests and upgraded connections to finish. Server streams, that have received the whole
+/// request body, get `Disconnect`, and WebSocket connections are closed with code 1001.
+/// Connections, that are still active after shutdown timeout, are aborted.
+pub struct Server<M, A = Identity> {
+    listener: Listener,
+    make_service: M,
+    adapter: A,
+    lifespan: Option<BoxService<LifespanServerStream>>,
+    protocol: HttpProtocol,
+    fallback: ErrorFallback,
+    shutdown_timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Duration,
+}
+
+impl<M> Server<M> {
+    pub fn new(listener: TcpListener, make_service: M) -> Self {
+        Self::with_listener(Listener::Tcp(listener), make_service)
+    }
+
+    /// Creates a server, that accepts connections on a Unix domain socket.
+    #[cfg(unix)]
+    pub fn new_unix(listener: UnixListener, make_service: M) -> Self {
+        let listener = Listener::Unix {
+            listener,
+            path: None,
+        };
+        Self::with_listener(listener, make_service)
+    }
+
+    fn with_listener(listener: Listener, make_service: M) -> Self {
+        Self {
+            listener,
+            make_service,
+            adapter: Identity::new(),
+            lifespan: None,
+            protocol: HttpProtocol::default(),
+            fallback: ErrorFallback::default(),
+            shutdown_timeout: Duration::from_secs(30),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Binds TCP listener to the address and creates a server.
+    pub async fn bind<A: ToSocketAddrs>(addr: A, make_service: M) -> io::Result<Self> {
+        Ok(Self::new(TcpListener::bind(addr).await?, make_service))
+    }
+
+    /// Binds Unix domain socket listener to the path and creates a server. Stale socket file
+    /// at the path, that refuses connections, is replaced, and a socket of a running server
+    /// results in [`io::ErrorKind::AddrInUse`]. The file is removed, when the server stops.
+    #[cfg(unix)]
+    pub fn bind_unix<P: AsRef<Path>>(path: P, make_service: M) -> io::Result<Self> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+
+        let listener = Listener::Unix {
+            listener: UnixListener::bind(path)?,
+            path: Some(path.to_owned()),
+        };
+        Ok(Self::with_listener(listener, make_service))
+    }
+
+    /// Binds Unix domain socket listener like [`Server::bind_unix`], and sets permissions of
+    /// the socket file, e.g. to allow a reverse proxy of another user to connect.
+    ///
+    /// Socket is bound in a private directory next to the path, and moved to the path after
+    /// the permissions are set, so that it is never accessible with the default ones.
+    #[cfg(unix)]
+    pub fn bind_unix_with_permissions<P: AsRef<Path>>(
+        path: P,
+        permissions: Permissions,
+        make_service: M,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "socket path has no file name")
+        })?;
+        let mut dir_name = std::ffi::OsString::from(".");
+        dir_name.push(file_name);
+        dir_name.push(format!(".{}", std::process::id()));
+        let dir = path.with_file_name(dir_name);
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+        let private_path = dir.join("socket");
+        let bound = UnixListener::bind(&private_path).and_then(|listener| {
+            fs::set_permissions(&private_path, permissions)?;
+            fs::rename(&private_path, path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_file(&private_path);
+        let _ = fs::remove_dir(&dir);
+
+        let listener = Listener::Unix {
+            listener: bound?,
+            path: Some(path.to_owned()),
+        };
+        Ok(Self::with_listener(listener, make_service))
+    }
+}
+
+/// Removes socket file at the path, if nobody listens on it.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {}
+        _ => return Ok(()),
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "socket is used by a running server",
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+impl<M, A> Server<M, A> {
+    /// Returns the TCP address, that server is listening on. Fails for Unix domain socket
+    /// listeners.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self.listener.local_addr()? {
+            Address::Tcp(addr) => Ok(addr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "server is not listening on TCP socket",
+            )),
+        }
+    }
+
+    /// Sets the layer, that wraps [`Servio2Hyper`] of each connection into the `hyper` service,
+    /// that serves it. For example, services with readiness are served with
+    /// `layer_fn(ReadyServio2Hyper::from)`.
+    pub fn with_adapter<L>(self, adapter: L) -> Server<M, L> {
+        Server {
+            listener: self.listener,
+            make_service: self.make_service,
+            adapter,
+            lifespan: self.lifespan,
+            protocol: self.protocol,
+            fallback: self.fallback,
+            shutdown_timeout: self.shutdown_timeout,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: self.tls_handshake_timeout,
+        }
+    }
+
+    /// Drives lifespan protocol of the service around serving connections. If the service fails
+    /// to start up, the server doesn't accept connections, and the error is returned from
+    /// [`Server::serve`].
+    pub fn with_lifespan<S>(mut self, service: S) -> Self
+    where
+        S: Service<LifespanServerStream> + Send + 'static,
+        S::AppStream: Send + 'static,
+        S::Error: Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        self.lifespan = Some(BoxService::new(service));
+        self
+    }
+
+    /// Sets HTTP protocol of connections. Defaults to [`HttpProtocol::Auto`].
+    pub fn with_protocol(mut self, protocol: HttpProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Sets the strategy of handling errors, that occur before the response has started.
+    pub fn with_fallback(mut self, fallback: ErrorFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Sets the time, that in-flight requests are given to finish on shutdown. Defaults to
+    /// 30 seconds.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Serves connections over TLS. Connections, that fail TLS handshake, are closed.
+    ///
+    /// The config can be reloaded with [`TlsConfig::reload_pem`] while the server is running.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Sets the time, that clients are given to complete TLS handshake. Connections, that don't
+    /// complete it in time, are closed. Defaults to 10 seconds.
+    #[cfg(feature = "tls")]
+    pub fn with_tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = timeout;
+        self
+    }
+
+    /// Serves connections until the task is cancelled.
+    pub async fn serve<S, B>(self) -> io::Result<()>
+    where
+        M: MakeService<ConnectionInfo, BodyServerStream> + Send + 'static,
+        M::Future: Send + 'static,
+        A: Layer<Servio2Hyper<M::Service>, Service = S> + Send + Sync + 'static,
+        S: HyperService<Request<IncomingBody>, Response = Response<B>> + Send + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: Body + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+    {
+        self.serve_with_shutdown(future::pending()).await
+    }
+
+    /// Serves connections until `signal` completes, then shuts down gracefully.
+    ///
+    /// Errors of lifespan protocol are returned as [`io::Error`], that wraps [`LifespanError`].
+    ///
+    /// [`LifespanError`]: crate::LifespanError
+    pub async fn serve_with_shutdown<S, B, F>(mut self, signal: F) -> io::Result<()>
+    where
+        M: MakeService<ConnectionInfo, BodyServerStream> + Send + 'static,
+        M::Future: Send + 'static,
+        A: Layer<Servio2Hyper<M::Service>, Service = S> + Send + Sync + 'static,
+        S: HyperService<Request<IncomingBody>, Response = Response<B>> + Send + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+        B: Body + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<BoxError>,
+        F: Future<Output = ()>,
+    {
+        let lifespan = match &mut self.lifespan {
+            Some(service) => Some(Lifespan::startup(service).await.map_err(lifespan_error)?),
+            None => None,
+        };
+
+        let server = self.listener.local_addr()?;
+        let (trigger, shutdown) = ShutdownSignal::new();
+        let (task_sender, mut tasks) = mpsc::unbounded();
+        let connector = Arc::new(Connector {
+            make_service: Mutex::new(self.make_service),
+            adapter: self.adapter,
+            fallback: self.fallback,
+            shutdown: shutdown.clone(),
+            tasks: TaskSender(task_sender),
+        });
+        let mut connections = JoinSet::new();
+        tokio::pin!(signal);
+
+        loop {
+            let accepted = tokio::select! {
+                accepted = self.listener.accept() => accepted,
+                _ = signal.as_mut() => break,
+                Some(task) = tasks.next() => {
+                    connections.spawn(task);
+                    continue;
+                }
+                Some(_) = connections.join_next() => continue,
+            };
+
+            // Errors of a single connection, like a reset before accept, don't stop the server.
+            // Running out of file descriptors is retried after a pause
+            let (stream, client) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::error!("failed to accept a connection: {e}");
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    continue;
+                }
+            };
+
+            let connector = connector.clone();
+            let info = ConnectionInfo::new(Some(server.clone()), Some(client));
+            let (protocol, shutdown) = (self.protocol, shutdown.clone());
+            #[cfg(feature = "tls")]
+            let (tls, tls_handshake_timeout) = (self.tls.clone(), self.tls_handshake_timeout);
+
+            connections.spawn(async move {
+                #[cfg(feature = "tls")]
+                if let Some(tls) = tls {
+                    let handshake = tokio::time::timeout(tls_handshake_timeout, tls.accept(stream));
+                    let Ok(Ok((stream, tls_scope))) = handshake.await else {
+                        return;
+                    };
+                    // Protocol, negotiated with ALPN, takes precedence over server protocol
+                    let protocol = match &tls_scope.alpn_protocol {
+                        Some(alpn) => HttpProtocol::from_alpn(alpn),
+                        None => protocol,
+                    };
+                    let mut info = info;
+                    info.tls = Some(tls_scope);
+                    if let Some(service) = connector.connect(info).await {
+                        let _ = serve_connection_with_shutdown(stream, service, protocol, shutdown)
+                            .await;
+                    }
+                    return;
+                }
+
+                if let Some(service) = connector.connect(info).await {
+                    let _ =
+                        serve_connection_with_shutdown(stream, service, protocol, shutdown).await;
+                }
+            });
+        }
+
+        drop(self.listener);
+        let _ = trigger.send(());
+
+        // Connections may pass upgraded connections to the server until they're closed
+        let drain = async {
+            loop {
+                tokio::select! {
+                    biased;
+                    Some(task) = tasks.next() => {
+                        connections.spawn(task);
+                    }
+                    joined = connections.join_next() => {
+                        if joined.is_none() {
+                            break;
+                        }
+                    }
+                }
+            }
+        };
+        if tokio::time::timeout(self.shutdown_timeout, drain)
+            .await
+            .is_err()
+        {
+            connections.shutdown().await;
+        }
+
+        if let Some(lifespan) = lifespan {
+            lifespan.shutdown().await.map_err(lifespan_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates services for connections of a [`Server`].
+struct Connector<M, A> {
+    make_service: Mutex<M>,
+    adapter: A,
+    fallback: ErrorFallback,
+    shutdown: ShutdownSignal,
+    tasks: TaskSender,
+}
+
+impl<M, A> Connector<M, A> {
+    /// Makes a service for the connection and wraps it with the adapter. Returns `None`, if the
+    /// service can't be made.
+    async fn connect<S>(&self, info: ConnectionInfo) -> Option<S>
+    where
+        M: MakeService<ConnectionInfo, BodyServerStream>,
+        A: Layer<Servio2Hyper<M::Service>, Service = S>,
+    {
+        let make = self.make_service.lock().unwrap().make_service(info.clone());
+        match make.await {
+            Ok(service) => {
+                let service = Servio2Hyper::from_info(service, info)
+                    .with_fallback(self.fallback.clone())
+                    .with_shutdown(self.shutdown.clone())
+                    .with_tasks(self.tasks.clone());
+                Some(self.adapter.layer(service))
+            }
+            Err(e) => {
+                tracing::error!(client = ?info.client, "failed to make a service: {e}");
+                None
+            }
+        }
+    }
+}
+
+fn lifespan_error(e: crate::LifespanError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
@@ -0,0 +1,255 @@
+use bytes::Bytes;
+use servio_http::tls::TlsScope;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello,
+    ResolvesServerCert,
+};
+use tokio_rustls::rustls::sign::{self, CertifiedKey};
+use tokio_rustls::rustls::{
+    self, Certificate, PrivateKey, RootCertStore, ServerConfig, ServerConnection,
+};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Error of loading TLS configuration.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum TlsError {
+    /// PEM data could not be parsed.
+    Pem(io::Error),
+    /// PEM data contains no certificates.
+    MissingCertificate,
+    /// PEM data contains no private key.
+    MissingPrivateKey,
+    /// Configuration was rejected by `rustls`.
+    Rustls(rustls::Error),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pem(e) => write!(f, "invalid PEM: {e}"),
+            Self::MissingCertificate => f.write_str("no certificate found"),
+            Self::MissingPrivateKey => f.write_str("no private key found"),
+            Self::Rustls(e) => write!(f, "invalid TLS configuration: {e}"),
+        }
+    }
+}
+
+impl StdError for TlsError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Pem(e) => Some(e),
+            Self::Rustls(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Client certificate authentication mode.
+#[derive(Clone, Debug, Default)]
+pub enum ClientAuth {
+    /// Client certificates are not requested.
+    #[default]
+    None,
+    /// Client certificates are requested, but clients without one are accepted too.
+    Optional(RootCertStore),
+    /// Clients must present a certificate, issued by one of the roots.
+    Required(RootCertStore),
+}
+
+impl ClientAuth {
+    /// Creates [`ClientAuth::Optional`] with roots from PEM-encoded CA certificates.
+    pub fn optional(ca_certs: &[u8]) -> Result<Self, TlsError> {
+        Ok(Self::Optional(read_roots(ca_certs)?))
+    }
+
+    /// Creates [`ClientAuth::Required`] with roots from PEM-encoded CA certificates.
+    pub fn required(ca_certs: &[u8]) -> Result<Self, TlsError> {
+        Ok(Self::Required(read_roots(ca_certs)?))
+    }
+}
+
+/// TLS configuration of a server, backed by `rustls`. It is cheap to clone, and clones share
+/// the configuration, so it can be reloaded while the server is running.
+///
+/// Configurations, created from PEM, advertise `h2` and `http/1.1` with ALPN. Connections,
+/// that negotiated a protocol with ALPN, are served with it regardless of the server protocol.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    state: Arc<RwLock<TlsState>>,
+}
+
+#[derive(Debug)]
+struct TlsState {
+    config: Arc<ServerConfig>,
+    server_cert: Option<Bytes>,
+}
+
+impl TlsConfig {
+    /// Creates configuration from `rustls` server config.
+    ///
+    /// Certificate, that is presented to clients, is not known in this case, so it is missing
+    /// from [`TlsScope`].
+    pub fn new(config: Arc<ServerConfig>) -> Self {
+        let state = TlsState {
+            config,
+            server_cert: None,
+        };
+        Self {
+            state: Arc::new(RwLock::new(state)),
+        }
+    }
+
+    /// Creates configuration from PEM-encoded certificate chain and private key.
+    pub fn from_pem(cert_chain: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        Self::from_pem_with_client_auth(cert_chain, key, ClientAuth::None)
+    }
+
+    /// Creates configuration from PEM-encoded certificate chain and private key, that
+    /// authenticates clients with certificates.
+    pub fn from_pem_with_client_auth(
+        cert_chain: &[u8],
+        key: &[u8],
+        client_auth: ClientAuth,
+    ) -> Result<Self, TlsError> {
+        let (certified_key, server_cert) = read_certified_key(cert_chain, key)?;
+
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match client_auth {
+            ClientAuth::None => builder.with_no_client_auth(),
+            ClientAuth::Optional(roots) => builder.with_client_cert_verifier(
+                AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            ),
+            ClientAuth::Required(roots) => {
+                builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+            }
+        };
+        let mut config = builder.with_cert_resolver(Arc::new(SingleCert(certified_key)));
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let state = TlsState {
+            config: Arc::new(config),
+            server_cert: Some(server_cert),
+        };
+        Ok(Self {
+            state: Arc::new(RwLock::new(state)),
+        })
+    }
+
+    /// Replaces `rustls` server config. Established connections are not affected.
+    ///
+    /// Like with [`TlsConfig::new`], certificate, that is presented to clients, is not known
+    /// after the reload, so it is missing from [`TlsScope`]. Use [`TlsConfig::reload_pem`] to
+    /// replace only the certificate and keep it in [`TlsScope`].
+    pub fn reload(&self, config: Arc<ServerConfig>) {
+        *self.state.write().unwrap() = TlsState {
+            config,
+            server_cert: None,
+        };
+    }
+
+    /// Replaces certificate chain and private key, keeping the rest of configuration, like
+    /// client authentication mode and ALPN protocols. Established connections are not affected.
+    ///
+    /// If the new certificate can't be loaded, an error is returned and the old one is kept.
+    pub fn reload_pem(&self, cert_chain: &[u8], key: &[u8]) -> Result<(), TlsError> {
+        let (certified_key, server_cert) = read_certified_key(cert_chain, key)?;
+
+        let mut state = self.state.write().unwrap();
+        let mut config = ServerConfig::clone(&state.config);
+        config.cert_resolver = Arc::new(SingleCert(certified_key));
+        *state = TlsState {
+            config: Arc::new(config),
+            server_cert: Some(server_cert),
+        };
+        Ok(())
+    }
+
+    /// Performs TLS handshake and returns encrypted stream with TLS details of the connection.
+    pub(crate) async fn accept<I>(&self, io: I) -> io::Result<(TlsStream<I>, TlsScope)>
+    where
+        I: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (config, server_cert) = {
+            let state = self.state.read().unwrap();
+            (state.config.clone(), state.server_cert.clone())
+        };
+
+        let stream = TlsAcceptor::from(config).accept(io).await?;
+        let tls_scope = make_tls_scope(stream.get_ref().1, server_cert);
+        Ok((stream, tls_scope))
+    }
+}
+
+/// Resolves to the same certificate for every client.
+struct SingleCert(Arc<CertifiedKey>);
+
+impl ResolvesServerCert for SingleCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+}
+
+/// Reads certificate chain and private key, returning them with the leaf certificate.
+fn read_certified_key(
+    cert_chain: &[u8],
+    key: &[u8],
+) -> Result<(Arc<CertifiedKey>, Bytes), TlsError> {
+    let cert_chain = read_certs(cert_chain)?;
+    let key = read_key(key)?;
+    let server_cert = Bytes::copy_from_slice(&cert_chain[0].0);
+    let key = sign::any_supported_type(&key)
+        .map_err(|_| TlsError::Rustls(rustls::Error::General("invalid private key".into())))?;
+    Ok((Arc::new(CertifiedKey::new(cert_chain, key)), server_cert))
+}
+
+fn read_roots(pem: &[u8]) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in read_certs(pem)? {
+        roots.add(&cert).map_err(TlsError::Rustls)?;
+    }
+    Ok(roots)
+}
+
+fn read_certs(pem: &[u8]) -> Result<Vec<Certificate>, TlsError> {
+    let certs = rustls_pemfile::certs(&mut &*pem).map_err(TlsError::Pem)?;
+    if certs.is_empty() {
+        return Err(TlsError::MissingCertificate);
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(pem: &[u8]) -> Result<PrivateKey, TlsError> {
+    use rustls_pemfile::Item;
+
+    for item in rustls_pemfile::read_all(&mut &*pem).map_err(TlsError::Pem)? {
+        if let Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) = item {
+            return Ok(PrivateKey(key));
+        }
+    }
+    Err(TlsError::MissingPrivateKey)
+}
+
+fn make_tls_scope(conn: &ServerConnection, server_cert: Option<Bytes>) -> TlsScope {
+    let mut tls_scope = TlsScope::default();
+    tls_scope.server_cert = server_cert;
+    tls_scope.client_cert_chain = conn
+        .peer_certificates()
+        .unwrap_or_default()
+        .iter()
+        .map(|cert| Bytes::copy_from_slice(&cert.0))
+        .collect();
+    tls_scope.tls_version = conn.protocol_version().map(|version| version.get_u16());
+    tls_scope.cipher_suite = conn
+        .negotiated_cipher_suite()
+        .map(|suite| suite.suite().get_u16());
+    tls_scope.alpn_protocol = conn.alpn_protocol().map(Bytes::copy_from_slice);
+    tls_scope.server_name = conn.server_name().map(str::to_owned);
+    tls_scope
+}
@@ -1,284 +1,821 @@
-use crate::{BoxBody, BoxError, Servio2Hyper};
+use crate::deflate::{Deflate, DeflateConfig, DeflateParams, PERMESSAGE_DEFLATE};
+use crate::error::FullBody;
+use crate::fragment::{Fragments, MORE, TEXT};
+use crate::frame::{CloseError, FrameStream};
+use crate::{BodyServerStream, BoxBody, ResponseError, Servio2Hyper, ShutdownSignal, TaskSender};
 use bytes::Bytes;
+use flume::r#async::SendFut;
 use futures_core::future::BoxFuture;
-use futures_core::stream::BoxStream;
-use futures_core::{FusedStream, Stream};
-use futures_util::{SinkExt, StreamExt};
+use futures_core::Stream;
+use futures_util::{future, stream, FutureExt, SinkExt, StreamExt};
 use http::header::{
-    CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL,
-    SEC_WEBSOCKET_VERSION, UPGRADE,
+    CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY,
+    SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
 };
-use http::{HeaderValue, Request, Response, StatusCode};
+use http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, Version};
 use hyper::body::Incoming as IncomingBody;
-use hyper::body::{Body, Frame, SizeHint};
 use hyper::service::Service as HyperService;
-use hyper::upgrade::Upgraded;
-use servio_http::http::{EVENT_HTTP, PROTOCOL_HTTP};
+use hyper::upgrade::OnUpgrade;
+use servio_http::http::{Address, EVENT_HTTP};
 use servio_http::websocket::{
-    Accept, BinaryFrame, Close, Connect, TextFrame, WebSocketEvent, WebSocketScope, EVENT_WEBSOCKET,
+    BinaryFrame, Connect, Disconnect, Ping, Pong, TextFrame, WebSocketEvent, WebSocketExtension,
+    WebSocketFamily, WebSocketScope, PROTOCOL_WEBSOCKET,
 };
-use servio_service::{Event, Scope, Service};
+use servio_service::{Event, Layer, Scope, Service};
 use std::borrow::Cow;
-use std::error::Error;
+use std::error::Error as StdError;
+use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, BufWriter};
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::error::{Error as WsError, ProtocolError};
 use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
-use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::protocol::{
+    CloseFrame, Role, WebSocketConfig as TungsteniteConfig,
+};
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 
-struct Servio2HyperWebSocket<T> {
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 << 20;
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+const DEFAULT_SEND_QUEUE_SIZE: usize = 16;
+const DEFAULT_WRITE_BUFFER_SIZE: usize = 128 << 10;
+const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings of WebSocket connections, served by [`Servio2HyperWebSocket`].
+#[derive(Clone, Debug)]
+pub struct WebSocketConfig {
+    ping_events: bool,
+    fragment_events: bool,
+    max_frame_size: usize,
+    max_message_size: usize,
+    send_queue_size: usize,
+    write_buffer_size: usize,
+    idle_timeout: Option<Duration>,
+    close_timeout: Duration,
+    keepalive: Option<Keepalive>,
+    deflate: Option<DeflateConfig>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            ping_events: false,
+            fragment_events: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            send_queue_size: DEFAULT_SEND_QUEUE_SIZE,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            idle_timeout: None,
+            close_timeout: DEFAULT_CLOSE_TIMEOUT,
+            keepalive: None,
+            deflate: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl WebSocketConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends pings and pongs, received from the client, to the application as `Ping` and `Pong`
+    /// events. Pings are answered by the server either way. Disabled by default.
+    pub fn with_ping_events(mut self, enabled: bool) -> Self {
+        self.ping_events = enabled;
+        self
+    }
+
+    /// Sends messages of the client to the application frame by frame, as soon as each frame is
+    /// received. Otherwise, each message is sent as a single `TextFrame` or `BinaryFrame` event.
+    /// Disabled by default.
+    pub fn with_fragment_events(mut self, enabled: bool) -> Self {
+        self.fragment_events = enabled;
+        self
+    }
+
+    /// Sets maximum payload size of a frame, received from the client. Larger frames close the
+    /// connection with code 1009. Defaults to 16 MiB.
+    pub fn with_max_frame_size(mut self, size: usize) -> Self {
+        self.max_frame_size = size;
+        self
+    }
+
+    /// Sets maximum size of a message, received from the client. Larger messages close the
+    /// connection with code 1009. Defaults to 64 MiB.
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Sets number of client events, that are queued until the application reads them. Once the
+    /// queue is full, the server stops reading from the client, but keeps sending events of the
+    /// application. Defaults to 16.
+    pub fn with_send_queue_size(mut self, size: usize) -> Self {
+        self.send_queue_size = size;
+        self
+    }
+
+    /// Sets size of the buffer, that collects outgoing frames until they're flushed to the
+    /// connection. Defaults to 128 KiB.
+    pub fn with_write_buffer_size(mut self, size: usize) -> Self {
+        self.write_buffer_size = size;
+        self
+    }
+
+    /// Closes the connection with code 1001, if neither the application nor the client sends a
+    /// message within `timeout`. Pings and pongs don't count. Disabled by default.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets time, that the client has to complete closing handshake, once the connection is
+    /// closed. After it, the connection is dropped, and the application receives `Disconnect` with
+    /// code 1006, if the server has started the handshake. Defaults to 5 seconds.
+    pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    /// Pings the client after `interval` without incoming frames. If no frame arrives within
+    /// `timeout` after the ping, connection is dropped and the application receives `Disconnect`
+    /// with code 1006. Keepalive is paused, while the client isn't read because of the full
+    /// send queue. Disabled by default.
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some(Keepalive { interval, timeout });
+        self
+    }
+
+    /// Negotiates `permessage-deflate` compression with clients, that offer it, unless the
+    /// application accepts extensions in `Accept` event. Disabled by default.
+    pub fn with_deflate(mut self, config: DeflateConfig) -> Self {
+        self.deflate = Some(config);
+        self
+    }
+}
+
+/// Servio to `hyper` service wrapper with WebSocket support.
+///
+/// WebSocket upgrade requests are passed to the Servio service as `websocket` protocol scope,
+/// that contains `HttpScope` and `WebSocketScope`. Server stream starts with `Connect` event,
+/// and the application must respond with `Accept` to complete the handshake, or deny the
+/// connection with `Close` or HTTP response events. Other requests are handled like in
+/// [`Servio2Hyper`].
+///
+/// After the connection is closed by either side, `Disconnect` event with the close code is sent
+/// to the application, and server stream ends. App stream is read until it ends, but its events
+/// are ignored.
+///
+/// The application may send messages in fragments, by setting `more` flag of `TextFrame` and
+/// `BinaryFrame` events. Delivery of client messages, ping handling, keepalive and limits are
+/// configured with [`WebSocketConfig`]. Protocol violations and exceeded limits close the
+/// connection, and `Disconnect` event carries the close code, that was sent to the client.
+///
+/// [`Server`] serves connections with this wrapper, when [`WebSocketConfig`] is set as its
+/// adapter with [`Server::with_adapter`].
+///
+/// [`Server`]: crate::Server
+/// [`Server::with_adapter`]: crate::Server::with_adapter
+pub struct Servio2HyperWebSocket<T> {
     inner: Servio2Hyper<T>,
+    config: WebSocketConfig,
+}
+
+impl<T> Servio2HyperWebSocket<T> {
+    pub fn new(service: T, server: Option<Address>, client: Option<Address>) -> Self {
+        Self::from(Servio2Hyper::new(service, server, client))
+    }
+
+    pub fn with_config(mut self, config: WebSocketConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Converts hyper upgrade request into Servio scope.
+    fn prepare(&self, req: &Request<IncomingBody>) -> Scope {
+        let mut http_scope = crate::make_http_scope(
+            req.method().clone(),
+            req.uri().clone(),
+            req.version(),
+            req.headers().clone(),
+            self.inner.server.clone(),
+            self.inner.client.clone(),
+        );
+        let scheme = if self.inner.tls.is_some() {
+            "wss"
+        } else {
+            "ws"
+        };
+        http_scope.scheme = scheme.into();
+
+        let mut ws_scope = WebSocketScope::default();
+        ws_scope.subprotocols = req
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| Cow::from(s.to_owned()))
+            .collect();
+        ws_scope.extensions = parse_extensions(req.headers());
+
+        let mut scope = Scope::new(PROTOCOL_WEBSOCKET.into());
+        if let Some(tls) = &self.inner.tls {
+            scope = scope.with_scope(tls.clone());
+        }
+        scope.with_scope(http_scope).with_scope(ws_scope)
+    }
+}
+
+/// Serves WebSocket connections of a [`Server`] with the config.
+///
+/// [`Server`]: crate::Server
+impl<T> Layer<Servio2Hyper<T>> for WebSocketConfig {
+    type Service = Servio2HyperWebSocket<T>;
+
+    fn layer(&self, inner: Servio2Hyper<T>) -> Self::Service {
+        Servio2HyperWebSocket::from(inner).with_config(self.clone())
+    }
+}
+
+impl<T> From<Servio2Hyper<T>> for Servio2HyperWebSocket<T> {
+    fn from(inner: Servio2Hyper<T>) -> Self {
+        Self {
+            inner,
+            config: WebSocketConfig::default(),
+        }
+    }
+}
+
+/// Returns `true`, if the request is a valid WebSocket upgrade request.
+fn is_upgrade(req: &Request<IncomingBody>) -> bool {
+    fn has_token(headers: &HeaderMap, name: http::header::HeaderName, token: &str) -> bool {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|h| h.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    let headers = req.headers();
+    req.method() == Method::GET
+        && req.version() == Version::HTTP_11
+        && has_token(headers, CONNECTION, "upgrade")
+        && has_token(headers, UPGRADE, "websocket")
+        && headers
+            .get(SEC_WEBSOCKET_VERSION)
+            .map_or(false, |h| h == "13")
+        && headers.contains_key(SEC_WEBSOCKET_KEY)
+}
+
+/// Parses extensions from `Sec-WebSocket-Extensions` headers.
+fn parse_extensions(headers: &HeaderMap) -> Vec<WebSocketExtension> {
+    headers
+        .get_all(SEC_WEBSOCKET_EXTENSIONS)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .filter_map(|offer| {
+            let mut parts = offer.split(';').map(str::trim);
+            let mut extension = WebSocketExtension::default();
+            extension.name = parts
+                .next()
+                .filter(|name| !name.is_empty())?
+                .to_owned()
+                .into();
+            extension.params = parts
+                .filter(|param| !param.is_empty())
+                .map(|param| match param.split_once('=') {
+                    Some((name, value)) => {
+                        let value = value.trim().trim_matches('"').to_owned();
+                        (name.trim().to_owned().into(), Some(value.into()))
+                    }
+                    None => (param.to_owned().into(), None),
+                })
+                .collect();
+            Some(extension)
+        })
+        .collect()
+}
+
+fn format_extension(extension: &WebSocketExtension) -> String {
+    let mut value = extension.name.to_string();
+    for (name, param) in &extension.params {
+        value.push_str("; ");
+        value.push_str(name);
+        if let Some(param) = param {
+            value.push('=');
+            value.push_str(param);
+        }
+    }
+    value
+}
+
+/// Returns `permessage-deflate` parameters, accepted by the application or negotiated by the
+/// server.
+fn negotiate_deflate(
+    req: &Request<IncomingBody>,
+    accepted: &[WebSocketExtension],
+    config: Option<&DeflateConfig>,
+) -> Result<Option<DeflateParams>, Cow<'static, str>> {
+    let offers = parse_extensions(req.headers());
+    let [extension] = accepted else {
+        if accepted.is_empty() {
+            return Ok(config.and_then(|config| config.negotiate(&offers)));
+        }
+        return Err("Accept with multiple extensions".into());
+    };
+
+    let offered = offers.iter().any(|offer| offer.name == extension.name);
+    if extension.name != PERMESSAGE_DEFLATE || !offered {
+        return Err(format!("Accept with unsupported extension {:?}", extension.name).into());
+    }
+    match DeflateParams::from_response(extension) {
+        Some(params) => Ok(Some(params)),
+        None => Err(format!("Accept with invalid extension {extension:?}").into()),
+    }
+}
+
+/// Server stream of a WebSocket connection. It starts with `Connect` event, followed by frames,
+/// received from the client, and ends with `Disconnect` event.
+pub struct WebSocketServerStream {
+    connect: Option<Event>,
+    rx: flume::r#async::RecvStream<'static, Event>,
+}
+
+impl WebSocketServerStream {
+    fn new(rx: flume::Receiver<Event>) -> Self {
+        let connect = WebSocketEvent::Connect(Connect::default());
+        Self {
+            connect: Some(Event::typed::<WebSocketFamily>(connect)),
+            rx: rx.into_stream(),
+        }
+    }
+}
+
+impl Stream for WebSocketServerStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(connect) = self.connect.take() {
+            return Poll::Ready(Some(connect));
+        }
+        self.rx.poll_next_unpin(cx)
+    }
 }
 
-impl<T> Servio2HyperWebSocket<T>
+/// Waits for `Accept` event and builds handshake response, or builds denial response, if the
+/// application has sent `Close` or `http` events instead. The connection is served in
+/// a separate task after the upgrade, that is passed to the server, if there's one.
+async fn accept<AS, E>(
+    mut app_stream: AS,
+    req: Request<IncomingBody>,
+    tx: flume::Sender<Event>,
+    shutdown: Option<ShutdownSignal>,
+    tasks: Option<TaskSender>,
+    config: WebSocketConfig,
+) -> Result<Response<BoxBody>, ResponseError<E>>
 where
-    T: Service<BoxStream<'static, Event>> + 'static,
+    AS: Stream<Item = Event> + Send + Unpin + 'static,
 {
-    pub fn can_upgrade(scope: &Request<IncomingBody>) -> bool {
-        scope.method() == http::Method::GET
-            && scope.version() >= http::Version::HTTP_11
-            && scope
-                .headers()
-                .get(CONNECTION)
-                .and_then(|h| h.to_str().ok())
-                .map(|h| {
-                    h.split(|c| c == ' ' || c == ',')
-                        .any(|p| p.eq_ignore_ascii_case("Upgrade"))
-                })
-                .unwrap_or(false)
-            && scope
-                .headers()
-                .get(UPGRADE)
-                .and_then(|h| h.to_str().ok())
-                .map(|h| h.eq_ignore_ascii_case("websocket"))
-                .unwrap_or(false)
-            && scope
-                .headers()
-                .get(SEC_WEBSOCKET_VERSION)
-                .map(|h| h == "13")
-                .unwrap_or(false)
-            && scope.headers().contains_key(SEC_WEBSOCKET_KEY)
-    }
-
-    // app_stream is already accepted
-    async fn handle_connection<S>(
-        mut app_stream: S,
-        channel_tx: flume::Sender<Event>,
-        mut ws_stream: WebSocketStream<Upgraded>,
-    ) where
-        S: Stream<Item = Event> + FusedStream + Send + Unpin + 'static,
-    {
-        loop {
-            tokio::select! {
-                e = app_stream.select_next_some() => {
-                    let e: Event = e;
-                    if e.family() == EVENT_WEBSOCKET {
-                        if let Some(event) = e.get::<WebSocketEvent>() {
-                            match event.as_ref() {
-                                WebSocketEvent::Accept(..) | WebSocketEvent::Connect(..) => panic!("unexpected message"),
-                                WebSocketEvent::TextFrame(TextFrame{data, ..}) => {let _ = ws_stream.send(Message::Text(data.clone())).await;}
-                                WebSocketEvent::BinaryFrame(BinaryFrame{data, ..}) => {let _ = ws_stream.send(Message::Binary(data.to_vec())).await;}
-                                WebSocketEvent::Close(Close{code, reason, ..}) => {
-                                    let reason = reason.clone().unwrap_or("".into());
-                                    let _ = ws_stream.close(Some(CloseFrame{code: (*code).into(), reason})).await;
-                                }
-                                _ => {}
-                            }
+    let Some(event) = app_stream.next().await else {
+        return Err(ResponseError::UnexpectedEnd);
+    };
+    if event.family() == EVENT_HTTP {
+        // Connection is denied with a custom response
+        let app_stream = stream::once(future::ready(event)).chain(app_stream);
+        return crate::build_response(app_stream, None).await;
+    }
+    let accept = match event.downcast::<WebSocketFamily>() {
+        Some(WebSocketEvent::Accept(accept)) => accept,
+        Some(WebSocketEvent::Close(_)) => {
+            let mut response = Response::new(Box::pin(FullBody::new(Bytes::new())) as BoxBody);
+            *response.status_mut() = StatusCode::FORBIDDEN;
+            return Ok(response);
+        }
+        Some(event) => return Err(ResponseError::UnexpectedEvent(format!("{event:?}").into())),
+        None => {
+            return Err(ResponseError::UnexpectedEvent(
+                event.family().to_owned().into(),
+            ))
+        }
+    };
+
+    let mut response = Response::new(Box::pin(FullBody::new(Bytes::new())) as BoxBody);
+    *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+    *response.headers_mut() = accept.headers.clone();
+
+    let headers = response.headers_mut();
+    headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+    headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+    if let Some(key) = req.headers().get(SEC_WEBSOCKET_KEY) {
+        let accept_key = derive_accept_key(key.as_bytes());
+        headers.insert(SEC_WEBSOCKET_ACCEPT, accept_key.parse().unwrap());
+    }
+    if let Some(subprotocol) = &accept.subprotocol {
+        let Ok(value) = HeaderValue::from_str(subprotocol) else {
+            let event = format!("Accept with invalid subprotocol {subprotocol:?}");
+            return Err(ResponseError::UnexpectedEvent(event.into()));
+        };
+        headers.insert(SEC_WEBSOCKET_PROTOCOL, value);
+    }
+
+    let deflate = negotiate_deflate(&req, &accept.extensions, config.deflate.as_ref())
+        .map_err(ResponseError::UnexpectedEvent)?;
+    if let Some(params) = deflate {
+        let value = format_extension(&params.to_extension());
+        headers.insert(SEC_WEBSOCKET_EXTENSIONS, value.parse().unwrap());
+    }
+
+    let on_upgrade: OnUpgrade = hyper::upgrade::on(req);
+    let session = async move {
+        let Ok(upgraded) = on_upgrade.await else {
+            let _ = tx.send_async(disconnect(1006)).await;
+            return;
+        };
+        let mut io: Box<dyn Io> =
+            Box::new(BufWriter::with_capacity(config.write_buffer_size, upgraded));
+        // Frames are limited as they're received, before they're rewritten by extensions
+        let mut max_frame_size = Some(config.max_frame_size);
+        if let Some(params) = deflate {
+            let deflate = Deflate::new(params, config.max_message_size);
+            io = Box::new(FrameStream::new(io, deflate, max_frame_size.take()));
+        }
+        if config.fragment_events {
+            let fragments = Fragments::new(config.max_message_size);
+            io = Box::new(FrameStream::new(io, fragments, max_frame_size.take()));
+        }
+
+        let ws_config = TungsteniteConfig {
+            max_frame_size,
+            // Fragmented messages are limited by `Fragments`
+            max_message_size: (!config.fragment_events).then_some(config.max_message_size),
+            ..Default::default()
+        };
+        let ws_stream = WebSocketStream::from_raw_socket(io, Role::Server, Some(ws_config)).await;
+        serve_websocket(app_stream, ws_stream, tx, shutdown, config).await;
+    };
+    match tasks {
+        Some(tasks) => tasks.spawn(session),
+        None => {
+            tokio::spawn(session);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Connection after the upgrade, with extensions applied.
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+/// Forwards frames between the application and the client until the connection is closed.
+async fn serve_websocket<AS, S>(
+    mut app_stream: AS,
+    mut ws_stream: WebSocketStream<S>,
+    tx: flume::Sender<Event>,
+    shutdown: Option<ShutdownSignal>,
+    config: WebSocketConfig,
+) where
+    AS: Stream<Item = Event> + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let shutdown = async move {
+        match shutdown {
+            Some(shutdown) => shutdown.await,
+            None => future::pending().await,
+        }
+    };
+    tokio::pin!(shutdown);
+
+    // Fires after `interval` without incoming frames, or after `timeout` once ping is sent.
+    // Paused while the client isn't read, as its pongs can't be received meanwhile
+    let ping_timer = tokio::time::sleep(config.keepalive.map_or(Duration::ZERO, |k| k.interval));
+    tokio::pin!(ping_timer);
+    let mut awaiting_pong = false;
+    // Fires after `idle_timeout` without messages from either side
+    let idle_timer = tokio::time::sleep(config.idle_timeout.unwrap_or_default());
+    tokio::pin!(idle_timer);
+
+    // Type of fragmented message, that is being sent by the application
+    let mut sending = None;
+    // Incomplete character at the end of the last text fragment, received from the client
+    let mut utf8_tail = Vec::new();
+    // Client event, that waits for a free place in the queue. The client isn't read meanwhile
+    let mut queued: Option<SendFut<'static, Event>> = None;
+
+    let code = loop {
+        let message = tokio::select! {
+            event = app_stream.next() => {
+                if let (Some(_), Some(timeout)) = (&event, config.idle_timeout) {
+                    idle_timer.as_mut().reset(Instant::now() + timeout);
+                }
+                match event {
+                    Some(event) => match event.downcast::<WebSocketFamily>() {
+                        Some(WebSocketEvent::TextFrame(frame))
+                            if !frame.more && sending.is_none() =>
+                        {
+                            Message::Text(frame.data.clone())
                         }
-                    }
-                },
-                e = ws_stream.select_next_some() => {
-                    let e: Option<Message> = e.ok();
-
-                    let out_event = match e {
-                        Some(Message::Text(s)) => {
-                            let mut event = TextFrame::default();
-                            event.data = s;
-                            Some(WebSocketEvent::TextFrame(event))
+                        Some(WebSocketEvent::BinaryFrame(frame))
+                            if !frame.more && sending.is_none() =>
+                        {
+                            Message::Binary(frame.data.to_vec())
                         }
-                        Some(Message::Binary(b)) => {
-                            let mut event = BinaryFrame::default();
-                            event.data = b.into();
-                            Some(WebSocketEvent::BinaryFrame(event))
+                        Some(WebSocketEvent::TextFrame(frame)) => {
+                            fragment(&mut sending, Data::Text, frame.data.as_bytes(), frame.more)
                         }
-                        Some(Message::Close(Some(CloseFrame{code, reason}))) => {
-                            let mut event = Close::default();
-                            event.code = code.into();
-                            event.reason = Some(reason);
-                            Some(WebSocketEvent::Close(event))
+                        Some(WebSocketEvent::BinaryFrame(frame)) => {
+                            fragment(&mut sending, Data::Binary, &frame.data, frame.more)
                         }
-                        Some(Message::Close(None)) => {
-                            Some(WebSocketEvent::Close(Close::default()))
+                        Some(WebSocketEvent::Ping(ping)) => Message::Ping(ping.data.to_vec()),
+                        Some(WebSocketEvent::Pong(pong)) => Message::Pong(pong.data.to_vec()),
+                        Some(WebSocketEvent::Close(close)) => {
+                            close_message(close.code, &close.reason)
                         }
-                        // handle end of stream
-                        None => break,
-                        _ => None
-                    };
-
-                    if let Some(event) = out_event {
-                        channel_tx.send_async(Event::new(EVENT_WEBSOCKET.into(), event)).await.unwrap();
+                        // Any other event violates the protocol
+                        _ => close_message(1011, &None),
+                    },
+                    // App stream has ended without closing the connection
+                    None => close_message(1000, &None),
+                }
+            }
+            sent = async { queued.as_mut().unwrap().await }, if queued.is_some() => {
+                queued = None;
+                if let Some(keepalive) = config.keepalive {
+                    ping_timer.as_mut().reset(Instant::now() + keepalive.interval);
+                    awaiting_pong = false;
+                }
+                match sent {
+                    Ok(()) => continue,
+                    // The application has dropped server stream
+                    Err(_) => close_message(1001, &None),
+                }
+            }
+            message = ws_stream.next(), if queued.is_none() => {
+                if let (Some(Ok(_)), Some(keepalive)) = (&message, config.keepalive) {
+                    ping_timer.as_mut().reset(Instant::now() + keepalive.interval);
+                    awaiting_pong = false;
+                }
+                if let (Some(Ok(Message::Text(_) | Message::Binary(_))), Some(timeout)) =
+                    (&message, config.idle_timeout)
+                {
+                    idle_timer.as_mut().reset(Instant::now() + timeout);
+                }
+                match message {
+                    Some(Ok(Message::Text(data))) => {
+                        let mut frame = TextFrame::default();
+                        frame.data = data;
+                        let event = WebSocketEvent::TextFrame(frame);
+                        queued = Some(queue(&tx, event));
+                        continue;
+                    }
+                    Some(Ok(Message::Binary(data))) if config.fragment_events => {
+                        match fragment_event(&mut utf8_tail, data) {
+                            Some(event) => {
+                                queued = Some(queue(&tx, event));
+                                continue;
+                            }
+                            None => close_message(1007, &None),
+                        }
+                    }
+                    Some(Ok(Message::Binary(data))) => {
+                        let mut frame = BinaryFrame::default();
+                        frame.data = data.into();
+                        let event = WebSocketEvent::BinaryFrame(frame);
+                        queued = Some(queue(&tx, event));
+                        continue;
                     }
+                    Some(Ok(Message::Close(frame))) => {
+                        // Close reply is sent by `tungstenite` while the stream is drained
+                        let drain = drain(&mut ws_stream);
+                        let _ = tokio::time::timeout(config.close_timeout, drain).await;
+                        break frame.map_or(1005, |frame| frame.code.into());
+                    }
+                    // Pings are answered by `tungstenite`
+                    Some(Ok(Message::Ping(data))) if config.ping_events => {
+                        let mut ping = Ping::default();
+                        ping.data = data.into();
+                        let event = WebSocketEvent::Ping(ping);
+                        queued = Some(queue(&tx, event));
+                        continue;
+                    }
+                    Some(Ok(Message::Pong(data))) if config.ping_events => {
+                        let mut pong = Pong::default();
+                        pong.data = data.into();
+                        let event = WebSocketEvent::Pong(pong);
+                        queued = Some(queue(&tx, event));
+                        continue;
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => match error_code(&e) {
+                        1006 => break 1006,
+                        // Let the client know, why the connection is closed
+                        code => {
+                            let close = ws_stream.send(close_message(code, &None));
+                            let _ = tokio::time::timeout(config.close_timeout, close).await;
+                            break code;
+                        }
+                    },
+                    None => break 1006,
                 }
             }
-        }
-    }
+            _ = &mut ping_timer, if config.keepalive.is_some() && queued.is_none() => {
+                if awaiting_pong {
+                    // The client is unresponsive, drop the connection
+                    break 1006;
+                }
+                let keepalive = config.keepalive.unwrap();
+                ping_timer.as_mut().reset(Instant::now() + keepalive.timeout);
+                awaiting_pong = true;
+                Message::Ping(Vec::new())
+            }
+            _ = &mut idle_timer, if config.idle_timeout.is_some() => close_message(1001, &None),
+            _ = &mut shutdown => close_message(1001, &None),
+        };
 
-    async fn upgrade<S>(app_stream: S, req: Request<IncomingBody>, channel_tx: flume::Sender<Event>)
-    where
-        S: Stream<Item = Event> + Send + Unpin + 'static,
-    {
-        match hyper::upgrade::on(req).await {
-            Ok(upgraded) => {
-                Self::handle_connection(
-                    app_stream.fuse(),
-                    channel_tx,
-                    WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await,
-                )
-                .await;
+        let Message::Close(Some(frame)) = &message else {
+            if let Err(e) = ws_stream.send(message).await {
+                break error_code(&e);
             }
-            Err(e) => println!("upgrade error: {}", e),
-        }
-    }
+            continue;
+        };
 
-    async fn build_response<S>(
-        app_stream: S,
-        req: Request<IncomingBody>,
-        channel_tx: flume::Sender<Event>,
-    ) -> Result<Response<BoxBody>, BoxError>
-    where
-        S: Stream<Item = Event> + Send + Unpin + 'static,
-    {
-        let ws_event = WebSocketEvent::Connect(Connect::default());
-        channel_tx
-            .send_async(Event::new(EVENT_WEBSOCKET.into(), ws_event))
+        // The client must answer with `Close` and end the connection in time
+        let code = u16::from(frame.code);
+        let handshake = async {
+            match ws_stream.send(message).await {
+                Ok(()) => drain(&mut ws_stream).await.unwrap_or(code),
+                Err(e) => error_code(&e),
+            }
+        };
+        break tokio::time::timeout(config.close_timeout, handshake)
             .await
-            .unwrap();
-
-        let mut app_stream = app_stream.peekable();
-        let peeked: &Event = Pin::new(&mut app_stream).peek().await.unwrap();
-        match peeked.family() {
-            EVENT_HTTP => Servio2Hyper::<T>::build_response(app_stream).await,
-            EVENT_WEBSOCKET => {
-                let event = app_stream
-                    .next()
-                    .await
-                    .unwrap()
-                    .get::<WebSocketEvent>()
-                    .unwrap();
-                match event.as_ref() {
-                    WebSocketEvent::Accept(Accept {
-                        subprotocol,
-                        headers,
-                        ..
-                    }) => {
-                        let body: BoxBody = Box::pin(Empty {});
-
-                        let key = req.headers().get(SEC_WEBSOCKET_KEY);
-                        let derived = key.map(|k| derive_accept_key(k.as_bytes()));
-
-                        let response = {
-                            let mut res = Response::new(body);
-                            *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
-                            *res.version_mut() = req.version();
-
-                            *res.headers_mut() = headers.clone();
-
-                            res.headers_mut()
-                                .append(CONNECTION, HeaderValue::from_static("Upgrade"));
-                            res.headers_mut()
-                                .append(UPGRADE, HeaderValue::from_static("websocket"));
-                            res.headers_mut()
-                                .append(SEC_WEBSOCKET_ACCEPT, derived.unwrap().parse().unwrap());
-
-                            if let Some(subprotocol) = subprotocol {
-                                res.headers_mut().append(
-                                    SEC_WEBSOCKET_PROTOCOL,
-                                    subprotocol.to_string().parse().unwrap(),
-                                );
-                            }
-
-                            tokio::spawn(Self::upgrade(app_stream, req, channel_tx));
-
-                            res
-                        };
+            .unwrap_or(1006);
+    };
 
-                        Ok(response)
-                    }
-                    _ => panic!("Unexpected message type"),
-                }
-            }
-            _ => panic!("Unexpected event family"),
+    // Let the application read the queued events and `Disconnect`, and finish
+    let deliver = async move {
+        if let Some(queued) = queued {
+            let _ = queued.await;
         }
-    }
+        let _ = tx.into_send_async(disconnect(code)).await;
+    };
+    let finish = async { while app_stream.next().await.is_some() {} };
+    future::join(deliver, finish).await;
 }
 
-pub struct Empty {}
+/// Returns a future, that sends the event to the application.
+fn queue(tx: &flume::Sender<Event>, event: WebSocketEvent) -> SendFut<'static, Event> {
+    tx.clone()
+        .into_send_async(Event::typed::<WebSocketFamily>(event))
+}
 
-impl Body for Empty {
-    type Data = Bytes;
-    type Error = Box<dyn Error + Send + Sync>;
+/// Returns a frame of fragmented message of the application. If it continues a message of other
+/// type, returns close message instead.
+fn fragment(sending: &mut Option<Data>, data_type: Data, data: &[u8], more: bool) -> Message {
+    let opcode = match *sending {
+        None => OpCode::Data(data_type),
+        Some(sending) if sending == data_type => OpCode::Data(Data::Continue),
+        Some(_) => return close_message(1011, &None),
+    };
+    *sending = more.then_some(data_type);
+    Message::Frame(Frame::message(data.to_vec(), opcode, !more))
+}
 
-    #[inline]
-    fn poll_frame(
-        self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
-        Poll::Ready(None)
+/// Converts a frame, rewritten by [`Fragments`], into an event. Returns `None`, if the frame
+/// contains invalid UTF-8 text.
+fn fragment_event(utf8_tail: &mut Vec<u8>, data: Vec<u8>) -> Option<WebSocketEvent> {
+    let mut data = Bytes::from(data);
+    let flags = data.split_to(1)[0];
+    let more = flags & MORE != 0;
+    if flags & TEXT == 0 {
+        let mut frame = BinaryFrame::default();
+        frame.data = data;
+        frame.more = more;
+        return Some(WebSocketEvent::BinaryFrame(frame));
     }
 
-    fn is_end_stream(&self) -> bool {
-        true
+    let data = if utf8_tail.is_empty() {
+        data.to_vec()
+    } else {
+        utf8_tail.extend_from_slice(&data);
+        std::mem::take(utf8_tail)
+    };
+    let data = match String::from_utf8(data) {
+        Ok(data) => data,
+        // Character is split between frames
+        Err(e) if more && e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut data = e.into_bytes();
+            *utf8_tail = data.split_off(valid);
+            String::from_utf8(data).ok()?
+        }
+        Err(_) => return None,
+    };
+
+    let mut frame = TextFrame::default();
+    frame.data = data;
+    frame.more = more;
+    Some(WebSocketEvent::TextFrame(frame))
+}
+
+/// Reads the stream until it ends, and returns close code of the client, if it has sent one.
+async fn drain<S: AsyncRead + AsyncWrite + Unpin>(
+    ws_stream: &mut WebSocketStream<S>,
+) -> Option<u16> {
+    let mut code = None;
+    while let Some(Ok(message)) = ws_stream.next().await {
+        if let Message::Close(Some(frame)) = message {
+            code = Some(frame.code.into());
+        }
     }
+    code
+}
 
-    fn size_hint(&self) -> SizeHint {
-        SizeHint::with_exact(0)
+/// Returns close code for a connection error. Code 1006 means that the connection can't be
+/// closed properly.
+fn error_code(error: &WsError) -> u16 {
+    match error {
+        WsError::Capacity(_) => 1009,
+        WsError::Protocol(ProtocolError::ResetWithoutClosingHandshake) => 1006,
+        WsError::Protocol(_) => 1002,
+        WsError::Utf8 => 1007,
+        WsError::Io(e) => e
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<CloseError>())
+            .map_or(1006, |e| e.code),
+        _ => 1006,
     }
 }
 
-impl<T> HyperService<Request<IncomingBody>> for Servio2HyperWebSocket<T>
+fn close_message(code: u16, reason: &Option<Cow<'static, str>>) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: code.into(),
+        reason: reason.clone().unwrap_or_default(),
+    }))
+}
+
+fn disconnect(code: u16) -> Event {
+    let mut disconnect = Disconnect::default();
+    disconnect.code = code;
+    Event::typed::<WebSocketFamily>(WebSocketEvent::Disconnect(disconnect))
+}
+
+impl<T, E, F, AS, WF, WAS> HyperService<Request<IncomingBody>> for Servio2HyperWebSocket<T>
 where
-    T: Service<BoxStream<'static, Event>> + 'static,
+    E: StdError + Send + 'static,
+    AS: Stream<Item = Event> + Send + Unpin + 'static,
+    F: Future<Output = Result<AS, E>> + Send + 'static,
+    WAS: Stream<Item = Event> + Send + Unpin + 'static,
+    WF: Future<Output = Result<WAS, E>> + Send + 'static,
+    T: Service<BodyServerStream, Error = E, Future = F>,
+    T: Service<WebSocketServerStream, Error = E, Future = WF>,
 {
-    type Response =
-        Response<Pin<Box<dyn Body<Data = Bytes, Error = Box<dyn Error + Send + Sync>> + Send>>>;
-    type Error = BoxError;
+    type Response = Response<BoxBody>;
+    type Error = ResponseError<E>;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     fn call(&mut self, req: Request<IncomingBody>) -> Self::Future {
-        if !Self::can_upgrade(&req) {
+        if !is_upgrade(&req) {
             return self.inner.call(req);
         }
 
-        let http_scope = self.inner.make_http_scope(
-            req.method().clone(),
-            req.uri().clone(),
-            req.version(),
-            req.headers().clone(),
-        );
-
-        let mut ws_scope = WebSocketScope::default();
-        ws_scope.subprotocols = http_scope
-            .headers
-            .get_all(SEC_WEBSOCKET_PROTOCOL)
-            .iter()
-            .map(|h| h.to_str().unwrap())
-            .flat_map(|h| h.split(|c| c == ' ' || c == ','))
-            .map(|s| Cow::from(s.to_string()))
-            .collect();
-
-        // Prepare request
-        let scope = Scope::new(PROTOCOL_HTTP.into())
-            .with_scope(http_scope)
-            .with_scope(ws_scope);
+        let scope = self.prepare(&req);
+        let fallback = self.inner.fallback.clone();
+        let shutdown = self.inner.shutdown.clone();
+        let tasks = self.inner.tasks.clone();
+        let config = self.config.clone();
 
-        let (channel_tx, channel_rx) = flume::bounded(0);
-
-        let server_stream = Box::pin(channel_rx.into_stream());
+        let (tx, rx) = flume::bounded(config.send_queue_size);
+        let server_stream = WebSocketServerStream::new(rx);
 
         // Fire scope and server stream into the wrapped service, get app stream in return
-        let app_stream = self.inner.inner.call(scope, server_stream).unwrap();
+        let app_stream_fut =
+            Service::<WebSocketServerStream>::call(&mut self.inner.inner, scope, server_stream);
 
-        Box::pin(Self::build_response(app_stream, req, channel_tx))
+        async move {
+            let result = async {
+                let app_stream = app_stream_fut.await.map_err(ResponseError::Service)?;
+                accept(app_stream, req, tx, shutdown, tasks, config).await
+            };
+            fallback.handle(result.await)
+        }
+        .boxed()
     }
 }
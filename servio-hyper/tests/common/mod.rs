@@ -0,0 +1,84 @@
+//! Helpers, that serve a service over an in-memory connection and send requests to it.
+#![allow(dead_code)]
+
+use bytes::{Bytes, BytesMut};
+use futures_util::future::poll_fn;
+use http::{HeaderMap, Request, Response, StatusCode, Version};
+use hyper::body::{Body, Incoming};
+use hyper::client;
+use hyper::service::Service as HyperService;
+use servio_hyper::{serve_connection, HttpProtocol, TokioExecutor};
+use std::error::Error as StdError;
+use std::pin::Pin;
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Collected response.
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub trailers: Option<HeaderMap>,
+}
+
+impl TestResponse {
+    pub async fn collect(response: Response<Incoming>) -> Result<Self, hyper::Error> {
+        let (parts, mut body) = response.into_parts();
+        let mut data = BytesMut::new();
+        let mut trailers = None;
+
+        while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+            let frame = frame?;
+            if let Some(chunk) = frame.data_ref() {
+                data.extend_from_slice(chunk);
+            } else if let Some(headers) = frame.into_trailers() {
+                trailers.get_or_insert_with(HeaderMap::new).extend(headers);
+            }
+        }
+
+        Ok(Self {
+            status: parts.status,
+            headers: parts.headers,
+            body: data.freeze(),
+            trailers,
+        })
+    }
+}
+
+/// Serves a single request over an in-memory connection. Client uses HTTP/2 with prior knowledge,
+/// if request version is HTTP/2, and HTTP/1 otherwise.
+pub async fn request<S, B, RB>(service: S, request: Request<RB>) -> Result<TestResponse, BoxError>
+where
+    S: HyperService<Request<Incoming>, Response = Response<B>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    RB: Body + Send + 'static,
+    RB::Data: Send,
+    RB::Error: Into<BoxError>,
+{
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let server = serve_connection(server_io, service, HttpProtocol::Auto);
+
+    let client = async move {
+        let response = if request.version() == Version::HTTP_2 {
+            let (mut sender, connection) = client::conn::http2::Builder::new()
+                .executor(TokioExecutor)
+                .handshake(client_io)
+                .await?;
+            tokio::spawn(connection);
+            sender.send_request(request).await?
+        } else {
+            let (mut sender, connection) = client::conn::http1::handshake(client_io).await?;
+            tokio::spawn(connection);
+            sender.send_request(request).await?
+        };
+        TestResponse::collect(response).await
+    };
+
+    let (_, response) = tokio::join!(server, client);
+    Ok(response?)
+}
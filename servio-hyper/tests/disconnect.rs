@@ -0,0 +1,108 @@
+mod common;
+
+use common::request;
+use futures_channel::oneshot;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::Request;
+use hyper::server::conn::http1;
+use servio_http::http::{HttpEvent, HttpFamily, ResponseChunk, ResponseStart};
+use servio_hyper::Servio2Hyper;
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Sends a response with a first chunk, optionally leaving it open forever. Reports received
+/// server events, when server stream ends.
+#[derive(Clone)]
+struct Recorder {
+    endless: bool,
+    report: Arc<Mutex<Option<oneshot::Sender<Vec<&'static str>>>>>,
+}
+
+impl Recorder {
+    fn new(endless: bool) -> (Self, oneshot::Receiver<Vec<&'static str>>) {
+        let (tx, rx) = oneshot::channel();
+        let report = Arc::new(Mutex::new(Some(tx)));
+        (Self { endless, report }, rx)
+    }
+}
+
+impl<SS> Service<SS> for Recorder
+where
+    SS: Stream<Item = Event> + Send + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, server_events: SS) -> Self::Future {
+        let report = self.report.lock().unwrap().take().unwrap();
+        tokio::spawn(async move {
+            let events = server_events
+                .map(|event| match event.downcast::<HttpFamily>() {
+                    Some(HttpEvent::RequestChunk(..)) => "RequestChunk",
+                    Some(HttpEvent::Disconnect(..)) => "Disconnect",
+                    _ => "other",
+                })
+                .collect()
+                .await;
+            let _ = report.send(events);
+        });
+
+        let mut chunk = ResponseChunk::default();
+        chunk.body = "data".into();
+        chunk.more = self.endless;
+
+        let events = stream::iter([
+            Event::typed::<HttpFamily>(HttpEvent::ResponseStart(ResponseStart::default())),
+            Event::typed::<HttpFamily>(HttpEvent::ResponseChunk(chunk)),
+        ]);
+        let app_stream = if self.endless {
+            events.chain(stream::pending()).boxed()
+        } else {
+            events.boxed()
+        };
+        future::ok(app_stream)
+    }
+}
+
+#[tokio::test]
+async fn disconnect_after_response() {
+    let (app, report) = Recorder::new(false);
+    let service = Servio2Hyper::new(app, None, None);
+
+    let response = request(
+        service,
+        Request::post("/").body(String::from("body")).unwrap(),
+    )
+    .await;
+    assert_eq!(response.unwrap().body, "data");
+    assert_eq!(report.await.unwrap(), ["RequestChunk", "Disconnect"]);
+}
+
+#[tokio::test]
+async fn disconnect_on_client_gone() {
+    let (app, report) = Recorder::new(true);
+    let service = Servio2Hyper::new(app, None, None);
+
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(http1::Builder::new().serve_connection(server_io, service));
+
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(client_io)
+        .await
+        .unwrap();
+    let connection = tokio::spawn(connection);
+    let response = sender
+        .send_request(Request::get("/").body(String::new()).unwrap())
+        .await
+        .unwrap();
+
+    // Client goes away in the middle of the response
+    drop(response);
+    drop(sender);
+    connection.abort();
+
+    assert_eq!(report.await.unwrap(), ["RequestChunk", "Disconnect"]);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use bytes::Bytes;
+use common::request;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, Iter};
+use http::{Request, Response, StatusCode};
+use servio_hyper::{ErrorFallback, Servio2Hyper};
+use servio_service::{Event, Scope, Service};
+use std::io;
+use std::vec::IntoIter;
+
+/// Application, that fails or sends scripted events.
+#[derive(Clone)]
+struct Script(Result<Vec<Event>, io::ErrorKind>);
+
+impl<SS: Stream<Item = Event>> Service<SS> for Script {
+    type AppStream = Iter<IntoIter<Event>>;
+    type Error = io::Error;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        future::ready(match &self.0 {
+            Ok(events) => Ok(stream::iter(events.clone())),
+            Err(kind) => Err(io::Error::from(*kind)),
+        })
+    }
+}
+
+fn get() -> Request<String> {
+    Request::get("/").body(String::new()).unwrap()
+}
+
+#[tokio::test]
+async fn empty_app_stream() {
+    let service = Servio2Hyper::new(Script(Ok(vec![])), None, None);
+
+    let response = request(service, get()).await.unwrap();
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(response.body.is_empty());
+}
+
+#[tokio::test]
+async fn service_error() {
+    let service = Servio2Hyper::new(Script(Err(io::ErrorKind::Other)), None, None);
+
+    let response = request(service, get()).await.unwrap();
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn abort_on_unexpected_event() {
+    let event = Event::new("lifespan".into(), ());
+    let service =
+        Servio2Hyper::new(Script(Ok(vec![event])), None, None).with_fallback(ErrorFallback::Abort);
+
+    assert!(request(service, get()).await.is_err());
+}
+
+#[tokio::test]
+async fn custom_fallback() {
+    let fallback = ErrorFallback::custom(|e| {
+        let mut response = Response::new(Bytes::from(e.to_string()));
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        Some(response)
+    });
+    let service = Servio2Hyper::new(Script(Ok(vec![])), None, None).with_fallback(fallback);
+
+    let response = request(service, get()).await.unwrap();
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body, "app stream ended before ResponseStart");
+}
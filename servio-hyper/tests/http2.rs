@@ -0,0 +1,79 @@
+mod common;
+
+use common::request;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, Iter};
+use http::{HeaderValue, Request, Version};
+use servio_http::http::{
+    HttpEvent, HttpFamily, HttpScope, ResponseChunk, ResponseStart, ResponseTrailer,
+};
+use servio_hyper::{HttpProtocol, Servio2Hyper};
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::vec::IntoIter;
+
+/// Responds with request version in headers and body, and with `x-done` trailer.
+#[derive(Clone)]
+struct Version2Body;
+
+impl<SS: Stream<Item = Event>> Service<SS> for Version2Body {
+    type AppStream = Iter<IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, _server_events: SS) -> Self::Future {
+        let version = format!("{:?}", scope.get_ref::<HttpScope>().unwrap().version);
+
+        let mut start = ResponseStart::default();
+        start.headers.insert("x-version", version.parse().unwrap());
+        start.trailers = true;
+
+        let mut chunk = ResponseChunk::default();
+        chunk.body = version.into();
+
+        let mut trailer = ResponseTrailer::default();
+        trailer
+            .headers
+            .insert("x-done", HeaderValue::from_static("yes"));
+
+        let events = vec![
+            HttpEvent::ResponseStart(start),
+            HttpEvent::ResponseChunk(chunk),
+            HttpEvent::ResponseTrailer(trailer),
+        ];
+        let events = events.into_iter().map(Event::typed::<HttpFamily>);
+        future::ok(stream::iter(events.collect::<Vec<_>>()))
+    }
+}
+
+#[tokio::test]
+async fn http2_prior_knowledge() {
+    let service = Servio2Hyper::new(Version2Body, None, None);
+    let req = Request::get("http://localhost/")
+        .version(Version::HTTP_2)
+        .body(String::new())
+        .unwrap();
+
+    let response = request(service, req).await.unwrap();
+    assert_eq!(response.headers["x-version"], "HTTP/2.0");
+    assert_eq!(response.body, "HTTP/2.0");
+    assert_eq!(response.trailers.unwrap()["x-done"], "yes");
+}
+
+#[tokio::test]
+async fn http1_auto() {
+    let service = Servio2Hyper::new(Version2Body, None, None);
+    let req = Request::get("/").body(String::new()).unwrap();
+
+    let response = request(service, req).await.unwrap();
+    assert_eq!(response.headers["x-version"], "HTTP/1.1");
+    assert_eq!(response.body, "HTTP/1.1");
+}
+
+#[test]
+fn alpn() {
+    assert_eq!(HttpProtocol::from_alpn(b"h2"), HttpProtocol::Http2);
+    assert_eq!(HttpProtocol::from_alpn(b"http/1.1"), HttpProtocol::Http1);
+    assert_eq!(HttpProtocol::from_alpn(b"spdy/3"), HttpProtocol::Auto);
+}
@@ -0,0 +1,108 @@
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, StreamExt};
+use http::{HeaderMap, StatusCode};
+use servio_http::lifespan::{
+    LifespanEvent, ShutdownComplete, StartupComplete, StartupFailed, EVENT_LIFESPAN,
+};
+use servio_hyper::{Lifespan, LifespanError, Server};
+use servio_service::{Event, Scope, Service};
+use servio_util::response::PlainTextResponse;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Application, that records received lifespan events and responds to them.
+#[derive(Clone, Default)]
+struct App {
+    fail_startup: bool,
+    log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl<SS> Service<SS> for App
+where
+    SS: Stream<Item = Event> + Send + Unpin + 'static,
+{
+    type AppStream = stream::BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, server_events: SS) -> Self::Future {
+        let fail_startup = self.fail_startup;
+        let log = self.log.clone();
+
+        let app_stream = server_events.filter_map(move |event| {
+            let event = event.get::<LifespanEvent>().unwrap();
+            let response = match event.as_ref() {
+                LifespanEvent::Startup(..) if fail_startup => {
+                    log.lock().unwrap().push("startup");
+                    let mut failed = StartupFailed::default();
+                    failed.message = "no database".into();
+                    Some(LifespanEvent::StartupFailed(failed))
+                }
+                LifespanEvent::Startup(..) => {
+                    log.lock().unwrap().push("startup");
+                    Some(LifespanEvent::StartupComplete(StartupComplete::default()))
+                }
+                LifespanEvent::Shutdown(..) => {
+                    log.lock().unwrap().push("shutdown");
+                    Some(LifespanEvent::ShutdownComplete(ShutdownComplete::default()))
+                }
+                _ => None,
+            };
+            future::ready(response.map(|e| Event::new(EVENT_LIFESPAN.into(), e)))
+        });
+
+        future::ok(app_stream.boxed())
+    }
+}
+
+#[tokio::test]
+async fn startup_and_shutdown() {
+    let mut app = App::default();
+
+    let lifespan = Lifespan::startup(&mut app).await.unwrap();
+    assert!(lifespan.is_supported());
+    assert_eq!(*app.log.lock().unwrap(), ["startup"]);
+
+    lifespan.shutdown().await.unwrap();
+    assert_eq!(*app.log.lock().unwrap(), ["startup", "shutdown"]);
+}
+
+#[tokio::test]
+async fn startup_failed() {
+    let mut app = App {
+        fail_startup: true,
+        ..Default::default()
+    };
+
+    let Err(LifespanError::StartupFailed(message)) = Lifespan::startup(&mut app).await else {
+        panic!("startup must fail");
+    };
+    assert_eq!(message, "no database");
+}
+
+#[tokio::test]
+async fn server_startup_failed() {
+    let app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+    let lifespan = App {
+        fail_startup: true,
+        ..Default::default()
+    };
+    let server = Server::bind("127.0.0.1:0", app)
+        .await
+        .unwrap()
+        .with_lifespan(lifespan);
+
+    let error = server.serve().await.unwrap_err();
+    let error = error.get_ref().unwrap().downcast_ref::<LifespanError>();
+    assert!(matches!(error, Some(LifespanError::StartupFailed(_))));
+}
+
+#[tokio::test]
+async fn unsupported() {
+    let mut app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+
+    let lifespan = Lifespan::startup(&mut app).await.unwrap();
+    assert!(!lifespan.is_supported());
+    lifespan.shutdown().await.unwrap();
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use futures_channel::oneshot;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::Request;
+use servio_http::http::{HttpEvent, HttpFamily, ResponseChunk, ResponseStart};
+use servio_hyper::ReadyServio2Hyper;
+use servio_service::{Event, Scope, Service};
+use servio_util::limit::ConcurrencyLimit;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Holds each response until it is released by the test.
+#[derive(Clone, Default)]
+struct Gate {
+    pending: Arc<Mutex<Vec<oneshot::Sender<()>>>>,
+}
+
+impl Gate {
+    fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    fn release(&self) {
+        let _ = self.pending.lock().unwrap().remove(0).send(());
+    }
+}
+
+impl<SS: Stream<Item = Event>> Service<SS> for Gate {
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().push(tx);
+
+        let response = async move {
+            let _ = rx.await;
+            vec![
+                Event::typed::<HttpFamily>(HttpEvent::ResponseStart(ResponseStart::default())),
+                Event::typed::<HttpFamily>(HttpEvent::ResponseChunk(ResponseChunk::default())),
+            ]
+        };
+        future::ok(stream::once(response).flat_map(stream::iter).boxed())
+    }
+}
+
+/// Waits until the gate holds `count` responses.
+async fn wait_pending(gate: &Gate, count: usize) {
+    while gate.pending() < count {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn saturated_service_holds_requests() {
+    let gate = Gate::default();
+    let app = ConcurrencyLimit::new(gate.clone(), 1);
+    let send = |app| {
+        let service = ReadyServio2Hyper::new(app, None, None);
+        let request = Request::get("/").body(String::new()).unwrap();
+        tokio::spawn(common::request(service, request))
+    };
+
+    let first = send(app.clone());
+    wait_pending(&gate, 1).await;
+
+    // The second request waits for the slot, held by the first one
+    let second = send(app);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(gate.pending(), 1);
+
+    gate.release();
+    first.await.unwrap().unwrap();
+    wait_pending(&gate, 1).await;
+    gate.release();
+    second.await.unwrap().unwrap();
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::TestResponse;
+use futures_channel::{mpsc, oneshot};
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::{HeaderMap, Request, StatusCode};
+use hyper::body::{Body, Frame};
+use servio_http::http::Address;
+use servio_http::http::{HttpEvent, HttpFamily, ResponseChunk, ResponseStart};
+use servio_http::lifespan::{
+    LifespanEvent, LifespanFamily, ShutdownComplete, StartupComplete, PROTOCOL_LIFESPAN,
+};
+use servio_hyper::{ConnectionInfo, ReadyServio2Hyper, Server, Servio2Hyper};
+use servio_service::{layer_fn, Event, Scope, Service};
+use servio_util::limit::ConcurrencyLimit;
+use servio_util::make::make_service_fn;
+use servio_util::response::PlainTextResponse;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Request body, that sends chunks from a channel until it is closed.
+struct ChannelBody(mpsc::UnboundedReceiver<Bytes>);
+
+impl Body for ChannelBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.0
+            .poll_next_unpin(cx)
+            .map(|chunk| chunk.map(|data| Ok(Frame::data(data))))
+    }
+}
+
+/// Reads request body until `Disconnect`, then responds with the body. Notifies, when the first
+/// chunk is read. Records lifespan and request events.
+#[derive(Clone, Default)]
+struct WaitDisconnect {
+    started: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl<SS> Service<SS> for WaitDisconnect
+where
+    SS: Stream<Item = Event> + Send + Unpin + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, mut server_events: SS) -> Self::Future {
+        if scope.protocol() == PROTOCOL_LIFESPAN {
+            return future::ok(self.lifespan(server_events));
+        }
+
+        let mut started = self.started.lock().unwrap().take();
+        let log = self.log.clone();
+
+        let response = async move {
+            let mut body = BytesMut::new();
+            while let Some(event) = server_events.next().await {
+                match event.downcast::<HttpFamily>() {
+                    Some(HttpEvent::RequestChunk(chunk)) => {
+                        if let Some(started) = started.take() {
+                            log.lock().unwrap().push("request");
+                            let _ = started.send(());
+                        }
+                        body.extend_from_slice(&chunk.body);
+                    }
+                    Some(HttpEvent::Disconnect(_)) => {
+                        log.lock().unwrap().push("disconnect");
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            let mut chunk = ResponseChunk::default();
+            chunk.body = body.freeze();
+            vec![
+                Event::typed::<HttpFamily>(HttpEvent::ResponseStart(ResponseStart::default())),
+                Event::typed::<HttpFamily>(HttpEvent::ResponseChunk(chunk)),
+            ]
+        };
+
+        future::ok(stream::once(response).flat_map(stream::iter).boxed())
+    }
+}
+
+impl WaitDisconnect {
+    fn lifespan<SS>(&self, server_events: SS) -> BoxStream<'static, Event>
+    where
+        SS: Stream<Item = Event> + Send + Unpin + 'static,
+    {
+        let log = self.log.clone();
+        server_events
+            .filter_map(move |event| {
+                let response = match event.downcast::<LifespanFamily>() {
+                    Some(LifespanEvent::Startup(_)) => {
+                        log.lock().unwrap().push("startup");
+                        LifespanEvent::StartupComplete(StartupComplete::default())
+                    }
+                    Some(LifespanEvent::Shutdown(_)) => {
+                        log.lock().unwrap().push("shutdown");
+                        LifespanEvent::ShutdownComplete(ShutdownComplete::default())
+                    }
+                    _ => return future::ready(None),
+                };
+                future::ready(Some(Event::typed::<LifespanFamily>(response)))
+            })
+            .boxed()
+    }
+}
+
+async fn send<B>(addr: SocketAddr, request: Request<B>) -> TestResponse
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+    tokio::spawn(connection);
+    let response = sender.send_request(request).await.unwrap();
+    TestResponse::collect(response).await.unwrap()
+}
+
+#[tokio::test]
+async fn serve_and_shutdown() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let server = Server::bind("127.0.0.1:0", app).await.unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve_with_shutdown(async {
+        let _ = signal.await;
+    }));
+
+    let response = send(addr, Request::get("/").body(String::new()).unwrap()).await;
+    assert_eq!(response.body, "Hello");
+
+    shutdown.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn upload_on_shutdown() {
+    let (started_tx, started_rx) = oneshot::channel();
+    let app = WaitDisconnect {
+        started: Arc::new(Mutex::new(Some(started_tx))),
+        ..Default::default()
+    };
+    let server = Server::bind("127.0.0.1:0", app.clone())
+        .await
+        .unwrap()
+        .with_lifespan(app.clone());
+    let addr = server.local_addr().unwrap();
+
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve_with_shutdown(async {
+        let _ = signal.await;
+    }));
+
+    let (body_tx, body_rx) = mpsc::unbounded();
+    let body = ChannelBody(body_rx);
+    let response = tokio::spawn(send(addr, Request::post("/").body(body).unwrap()));
+    body_tx.unbounded_send(Bytes::from("first ")).unwrap();
+    started_rx.await.unwrap();
+
+    // The body is received in full, then the app gets `Disconnect`
+    shutdown.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    body_tx.unbounded_send(Bytes::from("second")).unwrap();
+    drop(body_tx);
+
+    let response = response.await.unwrap();
+    assert_eq!(response.body, "first second");
+    server.await.unwrap().unwrap();
+    assert_eq!(
+        *app.log.lock().unwrap(),
+        ["startup", "request", "disconnect", "shutdown"]
+    );
+}
+
+#[tokio::test]
+async fn shutdown_timeout() {
+    let (started_tx, started_rx) = oneshot::channel();
+    let app = WaitDisconnect {
+        started: Arc::new(Mutex::new(Some(started_tx))),
+        ..Default::default()
+    };
+    let server = Server::bind("127.0.0.1:0", app)
+        .await
+        .unwrap()
+        .with_shutdown_timeout(Duration::from_millis(100));
+    let addr = server.local_addr().unwrap();
+
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve_with_shutdown(async {
+        let _ = signal.await;
+    }));
+
+    // The body never ends, so the connection is aborted
+    let (body_tx, body_rx) = mpsc::unbounded();
+    let body = ChannelBody(body_rx);
+    tokio::spawn(send(addr, Request::post("/").body(body).unwrap()));
+    body_tx.unbounded_send(Bytes::from("chunk")).unwrap();
+    started_rx.await.unwrap();
+
+    shutdown.send(()).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server must stop after shutdown timeout")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn ready_adapter() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let server = Server::bind("127.0.0.1:0", ConcurrencyLimit::new(app, 1))
+        .await
+        .unwrap()
+        .with_adapter(layer_fn(ReadyServio2Hyper::from));
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+
+    for _ in 0..2 {
+        let response = send(addr, Request::get("/").body(String::new()).unwrap()).await;
+        assert_eq!(response.body, "Hello");
+    }
+}
+
+#[tokio::test]
+async fn make_service() {
+    let infos = Arc::new(Mutex::new(Vec::new()));
+    let mut factory = {
+        let infos = infos.clone();
+        make_service_fn(move |info: ConnectionInfo| {
+            let body = format!("{:?}", info.client);
+            infos.lock().unwrap().push(info);
+            let app = PlainTextResponse::new(StatusCode::OK, body.into(), HeaderMap::default());
+            future::ok::<_, Infallible>(app)
+        })
+    };
+
+    let client = Address::Unix(None);
+    let info = ConnectionInfo::new(None, Some(client.clone()));
+    let service = Servio2Hyper::make(&mut factory, info).await.unwrap();
+    let request = Request::get("/").body(String::new()).unwrap();
+    let response = common::request(service, request).await.unwrap();
+    assert_eq!(response.body, "Some(Unix(None))");
+
+    let server = Server::bind("127.0.0.1:0", factory).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+    send(addr, Request::get("/").body(String::new()).unwrap()).await;
+
+    let infos = infos.lock().unwrap();
+    assert_eq!(infos.len(), 2);
+    assert_eq!(infos[1].server, Some(Address::Tcp(addr)));
+    assert!(matches!(infos[1].client, Some(Address::Tcp(_))));
+}
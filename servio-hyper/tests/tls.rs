@@ -0,0 +1,280 @@
+#![cfg(feature = "tls")]
+mod common;
+
+use common::TestResponse;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, Iter};
+use http::Request;
+use hyper::client::conn::{http1, http2};
+use servio_http::http::{HttpEvent, HttpFamily, HttpScope, ResponseChunk, ResponseStart};
+use servio_http::tls::TlsScope;
+use servio_hyper::{ClientAuth, ConnectionInfo, Server, TlsConfig, TokioExecutor};
+use servio_service::{Event, Scope, Service};
+use servio_util::make::make_service_fn;
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::vec::IntoIter;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::TlsConnector;
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Responds with request scheme, version and TLS details: server name, TLS version, number of
+/// client certificates, ALPN protocol and whether the server certificate is known.
+#[derive(Clone)]
+struct TlsInfo;
+
+impl<SS: Stream<Item = Event>> Service<SS> for TlsInfo {
+    type AppStream = Iter<IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, _server_events: SS) -> Self::Future {
+        let http_scope = scope.get_ref::<HttpScope>().unwrap();
+        let tls_scope = scope.get_ref::<TlsScope>().unwrap();
+
+        let mut chunk = ResponseChunk::default();
+        chunk.body = format!(
+            "{} {} {:?} {:#x} {} {} {}",
+            http_scope.scheme,
+            tls_scope.server_name.as_deref().unwrap_or("-"),
+            http_scope.version,
+            tls_scope.tls_version.unwrap_or_default(),
+            tls_scope.client_cert_chain.len(),
+            tls_scope
+                .alpn_protocol
+                .as_deref()
+                .map_or("-".into(), String::from_utf8_lossy),
+            tls_scope.server_cert.is_some(),
+        )
+        .into();
+
+        let events = vec![
+            HttpEvent::ResponseStart(ResponseStart::default()),
+            HttpEvent::ResponseChunk(chunk),
+        ];
+        let events = events.into_iter().map(Event::typed::<HttpFamily>);
+        future::ok(stream::iter(events.collect::<Vec<_>>()))
+    }
+}
+
+fn certificate() -> (rcgen::Certificate, Certificate) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let der = Certificate(cert.serialize_der().unwrap());
+    (cert, der)
+}
+
+fn pem(cert: &rcgen::Certificate) -> (Vec<u8>, Vec<u8>) {
+    let cert_pem = cert.serialize_pem().unwrap().into_bytes();
+    (cert_pem, cert.serialize_private_key_pem().into_bytes())
+}
+
+/// Client config, that trusts `root` and optionally authenticates with `client` certificate.
+fn client_config(root: &Certificate, client: Option<&rcgen::Certificate>) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add(root).unwrap();
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+
+    match client {
+        Some(cert) => {
+            let chain = vec![Certificate(cert.serialize_der().unwrap())];
+            let key = PrivateKey(cert.serialize_private_key_der());
+            builder.with_client_auth_cert(chain, key).unwrap()
+        }
+        None => builder.with_no_client_auth(),
+    }
+}
+
+async fn send(addr: SocketAddr, config: ClientConfig) -> Result<TestResponse, BoxError> {
+    let stream = TcpStream::connect(addr).await?;
+    let server_name = ServerName::try_from("localhost")?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await?;
+    let http2 = stream.get_ref().1.alpn_protocol() == Some(b"h2");
+
+    let request = Request::get("https://localhost/").body(String::new())?;
+    let response = if http2 {
+        let (mut sender, connection) = http2::Builder::new()
+            .executor(TokioExecutor)
+            .handshake(stream)
+            .await?;
+        tokio::spawn(connection);
+        sender.send_request(request).await?
+    } else {
+        let (mut sender, connection) = http1::handshake(stream).await?;
+        tokio::spawn(connection);
+        sender.send_request(request).await?
+    };
+    Ok(TestResponse::collect(response).await?)
+}
+
+async fn serve(config: TlsConfig) -> SocketAddr {
+    let server = Server::bind("127.0.0.1:0", TlsInfo)
+        .await
+        .unwrap()
+        .with_tls(config);
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+    addr
+}
+
+#[tokio::test]
+async fn tls_scope() {
+    let (cert, der) = certificate();
+    let (cert_pem, key_pem) = pem(&cert);
+    let addr = serve(TlsConfig::from_pem(&cert_pem, &key_pem).unwrap()).await;
+
+    let response = send(addr, client_config(&der, None)).await.unwrap();
+    assert_eq!(response.body, "https localhost HTTP/1.1 0x304 0 - true");
+}
+
+#[tokio::test]
+async fn make_service_with_tls() {
+    let (cert, der) = certificate();
+    let (cert_pem, key_pem) = pem(&cert);
+    let server_names = Arc::new(Mutex::new(Vec::new()));
+    let factory = {
+        let server_names = server_names.clone();
+        make_service_fn(move |info: ConnectionInfo| {
+            let tls = info.tls.unwrap_or_default();
+            server_names.lock().unwrap().push(tls.server_name);
+            future::ok::<_, Infallible>(TlsInfo)
+        })
+    };
+    let server = Server::bind("127.0.0.1:0", factory)
+        .await
+        .unwrap()
+        .with_tls(TlsConfig::from_pem(&cert_pem, &key_pem).unwrap());
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+
+    send(addr, client_config(&der, None)).await.unwrap();
+    assert_eq!(
+        *server_names.lock().unwrap(),
+        [Some("localhost".to_owned())]
+    );
+}
+
+#[tokio::test]
+async fn handshake_timeout() {
+    let (cert, _) = certificate();
+    let (cert_pem, key_pem) = pem(&cert);
+    let server = Server::bind("127.0.0.1:0", TlsInfo)
+        .await
+        .unwrap()
+        .with_tls(TlsConfig::from_pem(&cert_pem, &key_pem).unwrap())
+        .with_tls_handshake_timeout(Duration::from_millis(50));
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+
+    // Idle client is disconnected
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut [0; 1])).await;
+    assert_eq!(read.expect("connection must be closed").unwrap(), 0);
+}
+
+#[tokio::test]
+async fn alpn_http2() {
+    let (cert, der) = certificate();
+    let (cert_pem, key_pem) = pem(&cert);
+    let addr = serve(TlsConfig::from_pem(&cert_pem, &key_pem).unwrap()).await;
+
+    let mut config = client_config(&der, None);
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let response = send(addr, config).await.unwrap();
+    assert_eq!(response.body, "https localhost HTTP/2.0 0x304 0 h2 true");
+}
+
+#[tokio::test]
+async fn reload_certificate() {
+    let (old_cert, old_der) = certificate();
+    let (cert_pem, key_pem) = pem(&old_cert);
+    let config = TlsConfig::from_pem(&cert_pem, &key_pem).unwrap();
+    let addr = serve(config.clone()).await;
+    assert!(send(addr, client_config(&old_der, None)).await.is_ok());
+
+    let (new_cert, new_der) = certificate();
+    let (cert_pem, key_pem) = pem(&new_cert);
+    config.reload_pem(&cert_pem, &key_pem).unwrap();
+    assert!(config.reload_pem(b"invalid", &key_pem).is_err());
+
+    assert!(send(addr, client_config(&old_der, None)).await.is_err());
+    assert!(send(addr, client_config(&new_der, None)).await.is_ok());
+}
+
+#[tokio::test]
+async fn client_auth() {
+    let (cert, der) = certificate();
+    let (cert_pem, key_pem) = pem(&cert);
+    let client = rcgen::generate_simple_self_signed(vec!["client".into()]).unwrap();
+    let client_auth = ClientAuth::required(client.serialize_pem().unwrap().as_bytes()).unwrap();
+    let config = TlsConfig::from_pem_with_client_auth(&cert_pem, &key_pem, client_auth).unwrap();
+    let addr = serve(config).await;
+
+    assert!(send(addr, client_config(&der, None)).await.is_err());
+
+    let response = send(addr, client_config(&der, Some(&client)))
+        .await
+        .unwrap();
+    assert_eq!(response.body, "https localhost HTTP/1.1 0x304 1 - true");
+}
+
+#[tokio::test]
+async fn reload_keeps_settings() {
+    let (old_cert, old_der) = certificate();
+    let (new_cert, new_der) = certificate();
+    let client = rcgen::generate_simple_self_signed(vec!["client".into()]).unwrap();
+    let mut client_roots = RootCertStore::empty();
+    let client_der = Certificate(client.serialize_der().unwrap());
+    client_roots.add(&client_der).unwrap();
+    let mut server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(client_roots).boxed())
+        .with_single_cert(
+            vec![old_der.clone()],
+            PrivateKey(old_cert.serialize_private_key_der()),
+        )
+        .unwrap();
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let server_config = Arc::new(server_config);
+
+    let client_h2 = |root: &Certificate| {
+        let mut config = client_config(root, Some(&client));
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        config
+    };
+
+    // ALPN and client authentication of custom config are kept
+    let config = TlsConfig::new(server_config.clone());
+    let addr = serve(config.clone()).await;
+    let (cert_pem, key_pem) = pem(&new_cert);
+    config.reload_pem(&cert_pem, &key_pem).unwrap();
+    let response = send(addr, client_h2(&new_der)).await.unwrap();
+    assert_eq!(
+        response.body,
+        "https localhost HTTP/1.1 0x304 1 http/1.1 true"
+    );
+    assert!(send(addr, client_config(&new_der, None)).await.is_err());
+
+    // Certificate of `rustls` config is not known
+    let config = TlsConfig::from_pem(&cert_pem, &key_pem).unwrap();
+    let addr = serve(config.clone()).await;
+    config.reload(server_config);
+    let response = send(addr, client_h2(&old_der)).await.unwrap();
+    assert_eq!(
+        response.body,
+        "https localhost HTTP/1.1 0x304 1 http/1.1 false"
+    );
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use bytes::{Bytes, BytesMut};
+use common::request;
+use futures_core::Stream;
+use futures_util::future::{BoxFuture, FutureExt};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::{HeaderMap, HeaderValue, Request, Version};
+use hyper::body::{Body, Frame};
+use servio_http::http::{HttpEvent, HttpFamily, ResponseChunk, ResponseStart};
+use servio_hyper::Servio2Hyper;
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Request body with a single data frame, followed by trailers.
+struct TrailerBody {
+    data: Option<Bytes>,
+    trailers: Option<HeaderMap>,
+}
+
+impl Body for TrailerBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = match self.data.take() {
+            Some(data) => Some(Frame::data(data)),
+            None => self.trailers.take().map(Frame::trailers),
+        };
+        Poll::Ready(frame.map(Ok))
+    }
+}
+
+/// Echoes request body, sending request trailers in response headers.
+#[derive(Clone)]
+struct EchoTrailers;
+
+impl<SS> Service<SS> for EchoTrailers
+where
+    SS: Stream<Item = Event> + Send + Unpin + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, mut server_events: SS) -> Self::Future {
+        async move {
+            let mut body = BytesMut::new();
+            let mut start = ResponseStart::default();
+
+            while let Some(event) = server_events.next().await {
+                match event.downcast::<HttpFamily>() {
+                    Some(HttpEvent::RequestChunk(chunk)) => {
+                        body.extend_from_slice(&chunk.body);
+                        if !chunk.more {
+                            break;
+                        }
+                    }
+                    Some(HttpEvent::RequestTrailer(trailer)) => {
+                        start.headers.extend(trailer.headers.clone());
+                        if !trailer.more {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+
+            let mut chunk = ResponseChunk::default();
+            chunk.body = body.freeze();
+
+            let events = [
+                HttpEvent::ResponseStart(start),
+                HttpEvent::ResponseChunk(chunk),
+            ];
+            let events = events.into_iter().map(Event::typed::<HttpFamily>);
+            Ok(stream::iter(events).boxed())
+        }
+        .boxed()
+    }
+}
+
+#[tokio::test]
+async fn request_trailers() {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", HeaderValue::from_static("abc"));
+    let body = TrailerBody {
+        data: Some(Bytes::from("Hello")),
+        trailers: Some(trailers),
+    };
+
+    let service = Servio2Hyper::new(EchoTrailers, None, None);
+    let req = Request::post("http://localhost/")
+        .version(Version::HTTP_2)
+        .body(body)
+        .unwrap();
+
+    let response = request(service, req).await.unwrap();
+    assert_eq!(response.headers["x-checksum"], "abc");
+    assert_eq!(response.body, "Hello");
+}
@@ -0,0 +1,96 @@
+#![cfg(unix)]
+mod common;
+
+use common::TestResponse;
+use futures_channel::oneshot;
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, Iter};
+use http::Request;
+use servio_http::http::{Address, HttpEvent, HttpFamily, HttpScope, ResponseChunk, ResponseStart};
+use servio_hyper::Server;
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::fs::{self, Permissions};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::vec::IntoIter;
+use tokio::net::UnixStream;
+
+/// Responds with server and client addresses.
+#[derive(Clone)]
+struct Addresses;
+
+impl<SS: Stream<Item = Event>> Service<SS> for Addresses {
+    type AppStream = Iter<IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, _server_events: SS) -> Self::Future {
+        let http_scope = scope.get_ref::<HttpScope>().unwrap();
+        let unix_path = |address: &Option<Address>| match address {
+            Some(Address::Unix(Some(path))) => path.display().to_string(),
+            Some(Address::Unix(None)) => "unnamed".into(),
+            _ => "-".into(),
+        };
+
+        let mut chunk = ResponseChunk::default();
+        chunk.body = format!(
+            "{} {}",
+            unix_path(&http_scope.server),
+            unix_path(&http_scope.client)
+        )
+        .into();
+
+        let events = vec![
+            HttpEvent::ResponseStart(ResponseStart::default()),
+            HttpEvent::ResponseChunk(chunk),
+        ];
+        let events = events.into_iter().map(Event::typed::<HttpFamily>);
+        future::ok(stream::iter(events.collect::<Vec<_>>()))
+    }
+}
+
+#[tokio::test]
+async fn unix_socket() {
+    let path = std::env::temp_dir().join(format!("servio-{}.sock", std::process::id()));
+    let permissions = Permissions::from_mode(0o660);
+    let server = Server::bind_unix_with_permissions(&path, permissions, Addresses).unwrap();
+    assert!(server.local_addr().is_err());
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o660);
+
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve_with_shutdown(async {
+        let _ = signal.await;
+    }));
+
+    let stream = UnixStream::connect(&path).await.unwrap();
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+    tokio::spawn(connection);
+    let request = Request::get("/").body(String::new()).unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    let response = TestResponse::collect(response).await.unwrap();
+    assert_eq!(response.body, format!("{} unnamed", path.display()));
+
+    shutdown.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn stale_socket() {
+    let path = std::env::temp_dir().join(format!("servio-stale-{}.sock", std::process::id()));
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    // Stale socket is replaced, but a running server keeps its socket
+    let server = Server::bind_unix(&path, Addresses).unwrap();
+    let error = Server::bind_unix(&path, Addresses).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::AddrInUse);
+    assert!(UnixStream::connect(&path).await.is_ok());
+
+    drop(server);
+    assert!(!path.exists());
+}
@@ -0,0 +1,603 @@
+#![cfg(feature = "websocket")]
+mod common;
+
+use common::request;
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use futures_channel::{mpsc, oneshot};
+use futures_core::Stream;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use futures_util::SinkExt;
+use http::header::{
+    CONNECTION, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use http::{Request, StatusCode};
+use servio_http::http::{
+    HttpEvent, HttpFamily, HttpScope, ResponseChunk, ResponseStart, PROTOCOL_HTTP,
+};
+use servio_http::websocket::{
+    Accept, Close, TextFrame, WebSocketEvent, WebSocketExtension, WebSocketFamily, WebSocketScope,
+};
+use servio_hyper::{
+    serve_connection, DeflateConfig, HttpProtocol, Server, Servio2HyperWebSocket, WebSocketConfig,
+};
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::{CloseCode, Data, OpCode};
+use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+/// Accepts WebSocket connections with the first requested subprotocol and echoes frames.
+/// Text frame `close` makes it close the connection, and pings are answered with `ping <data>`
+/// text frames. Close codes of `Disconnect` events are reported to `disconnects`.
+///
+/// Connections to `/forbidden` are denied with `Close`, and connections to `/denied` with
+/// `401 Unauthorized` response. Connections to `/deflate` are accepted with
+/// `permessage-deflate; server_no_context_takeover` extension. Plain HTTP requests are answered with `plain` body.
+///
+/// Connections to `/unread` are accepted without reading server stream, and closed after
+/// a delayed `hello` text frame. Connections to `/dropped` are accepted after dropping server
+/// stream.
+#[derive(Clone)]
+struct EchoWebSocket {
+    disconnects: mpsc::UnboundedSender<u16>,
+}
+
+impl<SS> Service<SS> for EchoWebSocket
+where
+    SS: Stream<Item = Event> + Send + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let path = scope.get_ref::<HttpScope>().unwrap().path.clone();
+        if scope.protocol() == PROTOCOL_HTTP || path == "/denied" {
+            let mut start = ResponseStart::default();
+            let mut chunk = ResponseChunk::default();
+            if path == "/denied" {
+                start.status = StatusCode::UNAUTHORIZED;
+                chunk.body = "denied".into();
+            } else {
+                chunk.body = "plain".into();
+            }
+            let events = [
+                HttpEvent::ResponseStart(start),
+                HttpEvent::ResponseChunk(chunk),
+            ];
+            let events = events.into_iter().map(Event::typed::<HttpFamily>);
+            return future::ok(stream::iter(events).boxed());
+        }
+
+        let accept = Event::typed::<WebSocketFamily>(WebSocketEvent::Accept(Accept::default()));
+        if path == "/dropped" {
+            drop(server_events);
+            return future::ok(
+                stream::once(future::ready(accept))
+                    .chain(stream::pending())
+                    .boxed(),
+            );
+        }
+        if path == "/unread" {
+            let events = async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                let mut frame = TextFrame::default();
+                frame.data = "hello".into();
+                let events = [
+                    WebSocketEvent::TextFrame(frame),
+                    WebSocketEvent::Close(Close::default()),
+                ];
+                // Server stream is held until the app stream ends
+                stream::iter(events).map(move |event| {
+                    let _ = &server_events;
+                    Event::typed::<WebSocketFamily>(event)
+                })
+            };
+            let events = stream::once(future::ready(accept)).chain(stream::once(events).flatten());
+            return future::ok(events.boxed());
+        }
+
+        let subprotocol = scope
+            .get_ref::<WebSocketScope>()
+            .unwrap()
+            .subprotocols
+            .first()
+            .cloned();
+        let disconnects = self.disconnects.clone();
+
+        let events = server_events.filter_map(move |event| {
+            let reply = match event.downcast::<WebSocketFamily>() {
+                Some(WebSocketEvent::Connect(_)) if path == "/forbidden" => {
+                    Some(WebSocketEvent::Close(Close::default()))
+                }
+                Some(WebSocketEvent::Connect(_)) => {
+                    let mut accept = Accept::default();
+                    accept.subprotocol = subprotocol.clone();
+                    if path == "/deflate" {
+                        let mut extension = WebSocketExtension::default();
+                        extension.name = "permessage-deflate".into();
+                        extension.params = vec![("server_no_context_takeover".into(), None)];
+                        accept.extensions = vec![extension];
+                    }
+                    Some(WebSocketEvent::Accept(accept))
+                }
+                Some(WebSocketEvent::TextFrame(frame)) if frame.data == "close" => {
+                    let mut close = Close::default();
+                    close.code = 4000;
+                    close.reason = Some("bye".into());
+                    Some(WebSocketEvent::Close(close))
+                }
+                Some(WebSocketEvent::Ping(ping)) => {
+                    let mut frame = TextFrame::default();
+                    frame.data = format!("ping {}", String::from_utf8_lossy(&ping.data));
+                    Some(WebSocketEvent::TextFrame(frame))
+                }
+                Some(event @ (WebSocketEvent::TextFrame(_) | WebSocketEvent::BinaryFrame(_))) => {
+                    Some(event.clone())
+                }
+                Some(WebSocketEvent::Disconnect(disconnect)) => {
+                    let _ = disconnects.unbounded_send(disconnect.code);
+                    None
+                }
+                _ => None,
+            };
+            future::ready(reply.map(Event::typed::<WebSocketFamily>))
+        });
+        future::ok(events.boxed())
+    }
+}
+
+fn echo() -> (EchoWebSocket, mpsc::UnboundedReceiver<u16>) {
+    let (disconnects, rx) = mpsc::unbounded();
+    (EchoWebSocket { disconnects }, rx)
+}
+
+/// Serves the app over an in-memory connection and performs WebSocket handshake.
+async fn connect(
+    app: EchoWebSocket,
+    path: &str,
+    subprotocols: &str,
+    config: WebSocketConfig,
+) -> (WebSocketStream<DuplexStream>, Option<String>) {
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let service = Servio2HyperWebSocket::new(app, None, None).with_config(config);
+    tokio::spawn(serve_connection(server_io, service, HttpProtocol::Http1));
+
+    let mut request = format!("ws://localhost{path}")
+        .into_client_request()
+        .unwrap();
+    if !subprotocols.is_empty() {
+        let value = subprotocols.parse().unwrap();
+        request.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+    }
+
+    let (ws_stream, response) = tokio_tungstenite::client_async(request, client_io)
+        .await
+        .unwrap();
+    let subprotocol = response
+        .headers()
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .map(|h| h.to_str().unwrap().to_owned());
+    (ws_stream, subprotocol)
+}
+
+#[tokio::test]
+async fn echo_with_subprotocol() {
+    let (app, _) = echo();
+    let (mut ws_stream, subprotocol) =
+        connect(app, "/", "chat, superchat", WebSocketConfig::new()).await;
+    assert_eq!(subprotocol.as_deref(), Some("chat"));
+
+    ws_stream.send(Message::Text("hello".into())).await.unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("hello".into()));
+
+    ws_stream
+        .send(Message::Binary(vec![1, 2, 3]))
+        .await
+        .unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Binary(vec![1, 2, 3]));
+}
+
+#[tokio::test]
+async fn close_codes() {
+    // Closed by the application
+    let (app, mut disconnects) = echo();
+    let (mut ws_stream, _) = connect(app, "/", "", WebSocketConfig::new()).await;
+    ws_stream.send(Message::Text("close".into())).await.unwrap();
+
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::from(4000));
+    assert_eq!(frame.reason, "bye");
+    while ws_stream.next().await.is_some() {}
+    assert_eq!(disconnects.next().await, Some(4000));
+
+    // Closed by the client
+    let (app, mut disconnects) = echo();
+    let (mut ws_stream, _) = connect(app, "/", "", WebSocketConfig::new()).await;
+    let frame = CloseFrame {
+        code: CloseCode::from(4001),
+        reason: "".into(),
+    };
+    ws_stream.close(Some(frame)).await.unwrap();
+    while ws_stream.next().await.is_some() {}
+    assert_eq!(disconnects.next().await, Some(4001));
+}
+
+#[tokio::test]
+async fn ping_events() {
+    // Pings are answered by the server and not sent to the application
+    let (app, _) = echo();
+    let (mut ws_stream, _) = connect(app, "/", "", WebSocketConfig::new()).await;
+    ws_stream.send(Message::Ping(b"1".to_vec())).await.unwrap();
+    ws_stream.send(Message::Text("2".into())).await.unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Pong(b"1".to_vec()));
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("2".into()));
+
+    // Pings are answered by the server and sent to the application
+    let (app, _) = echo();
+    let config = WebSocketConfig::new().with_ping_events(true);
+    let (mut ws_stream, _) = connect(app, "/", "", config).await;
+    ws_stream.send(Message::Ping(b"1".to_vec())).await.unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Pong(b"1".to_vec()));
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("ping 1".into()));
+}
+
+#[tokio::test]
+async fn keepalive() {
+    let (app, mut disconnects) = echo();
+    let interval = Duration::from_millis(50);
+    let config = WebSocketConfig::new().with_keepalive(interval, interval);
+    let (mut ws_stream, _) = connect(app, "/", "", config).await;
+
+    // Pong is sent by the client on the next read
+    for _ in 0..2 {
+        let message = ws_stream.next().await.unwrap().unwrap();
+        assert_eq!(message, Message::Ping(Vec::new()));
+    }
+
+    // Unresponsive client gets disconnected
+    assert_eq!(disconnects.next().await, Some(1006));
+
+    // Client isn't dropped, while the queue of a slow application is full
+    let (app, _) = echo();
+    let config = WebSocketConfig::new()
+        .with_keepalive(Duration::from_millis(20), Duration::from_millis(20))
+        .with_send_queue_size(1);
+    let (mut ws_stream, _) = connect(app, "/unread", "", config).await;
+    for _ in 0..4 {
+        ws_stream
+            .send(Message::Text("ignored".into()))
+            .await
+            .unwrap();
+    }
+    let message = loop {
+        match ws_stream.next().await.unwrap().unwrap() {
+            Message::Ping(_) => continue,
+            message => break message,
+        }
+    };
+    assert_eq!(message, Message::Text("hello".into()));
+}
+
+#[tokio::test]
+async fn fragments() {
+    let (app, mut disconnects) = echo();
+    let config = WebSocketConfig::new()
+        .with_fragment_events(true)
+        .with_max_message_size(16);
+    let (mut ws_stream, _) = connect(app, "/", "", config).await;
+
+    // Echoed frame by frame, with the split character moved to the second frame
+    let first = Frame::message(
+        "caf\u{e9}".as_bytes()[..4].to_vec(),
+        OpCode::Data(Data::Text),
+        false,
+    );
+    let last = Frame::message(b"\xa9!".to_vec(), OpCode::Data(Data::Continue), true);
+    ws_stream.send(Message::Frame(first)).await.unwrap();
+    ws_stream.send(Message::Frame(last)).await.unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("caf\u{e9}!".into()));
+
+    // Messages over the limit close the connection
+    let first = Frame::message(vec![0; 10], OpCode::Data(Data::Binary), false);
+    let last = Frame::message(vec![0; 10], OpCode::Data(Data::Continue), true);
+    ws_stream.send(Message::Frame(first)).await.unwrap();
+    ws_stream.send(Message::Frame(last)).await.unwrap();
+    while let Some(Ok(_)) = ws_stream.next().await {}
+    assert_eq!(disconnects.next().await, Some(1009));
+}
+
+#[tokio::test]
+async fn limits() {
+    // Frames over the limit close the connection
+    let (app, mut disconnects) = echo();
+    let config = WebSocketConfig::new().with_max_frame_size(16);
+    let (mut ws_stream, _) = connect(app, "/", "", config).await;
+    ws_stream.send(Message::Binary(vec![0; 32])).await.unwrap();
+
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::Size);
+    while let Some(Ok(_)) = ws_stream.next().await {}
+    assert_eq!(disconnects.next().await, Some(1009));
+
+    // Idle connections are closed
+    let (app, mut disconnects) = echo();
+    let config = WebSocketConfig::new().with_idle_timeout(Duration::from_millis(50));
+    let (mut ws_stream, _) = connect(app, "/", "", config).await;
+
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::Away);
+    while ws_stream.next().await.is_some() {}
+    assert_eq!(disconnects.next().await, Some(1001));
+}
+
+#[tokio::test]
+async fn unread_events() {
+    // Frames of the application are sent, while the queue is full
+    let (app, _) = echo();
+    let config = WebSocketConfig::new().with_send_queue_size(1);
+    let (mut ws_stream, _) = connect(app, "/unread", "", config).await;
+    for _ in 0..4 {
+        ws_stream
+            .send(Message::Text("ignored".into()))
+            .await
+            .unwrap();
+    }
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("hello".into()));
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::Normal);
+
+    // Connection is closed, once the application has dropped server stream
+    let (app, _) = echo();
+    let (mut ws_stream, _) = connect(app, "/dropped", "", WebSocketConfig::new()).await;
+    ws_stream
+        .send(Message::Text("ignored".into()))
+        .await
+        .unwrap();
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::Away);
+}
+
+#[tokio::test]
+async fn close_timeout() {
+    let (app, mut disconnects) = echo();
+    let timeout = Duration::from_millis(50);
+    let config = WebSocketConfig::new()
+        .with_idle_timeout(timeout)
+        .with_close_timeout(timeout);
+    // The client never reads `Close` of the server
+    let (_ws_stream, _) = connect(app, "/", "", config).await;
+    assert_eq!(disconnects.next().await, Some(1006));
+}
+
+#[tokio::test]
+async fn server_shutdown() {
+    let (app, mut disconnects) = echo();
+    let server = Server::bind("127.0.0.1:0", app)
+        .await
+        .unwrap()
+        .with_adapter(WebSocketConfig::new());
+    let addr = server.local_addr().unwrap();
+
+    let (shutdown, signal) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve_with_shutdown(async {
+        let _ = signal.await;
+    }));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (mut ws_stream, _) = tokio_tungstenite::client_async("ws://localhost/", stream)
+        .await
+        .unwrap();
+    ws_stream.send(Message::Text("hello".into())).await.unwrap();
+    let message = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(message, Message::Text("hello".into()));
+
+    // The server waits for the connection to close
+    shutdown.send(()).unwrap();
+    let Some(Ok(Message::Close(Some(frame)))) = ws_stream.next().await else {
+        panic!("expected close frame");
+    };
+    assert_eq!(frame.code, CloseCode::Away);
+    while ws_stream.next().await.is_some() {}
+    server.await.unwrap().unwrap();
+    assert_eq!(disconnects.try_next().unwrap(), Some(1001));
+}
+
+#[tokio::test]
+async fn plain_http() {
+    let (app, _) = echo();
+    let service = Servio2HyperWebSocket::new(app, None, None);
+
+    let response = request(service, Request::get("/").body(String::new()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.body, "plain");
+}
+
+#[tokio::test]
+async fn denial() {
+    let upgrade_request = |uri| {
+        Request::get(uri)
+            .header(CONNECTION, "Upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(String::new())
+            .unwrap()
+    };
+
+    let (app, _) = echo();
+    let service = Servio2HyperWebSocket::new(app.clone(), None, None);
+    let response = request(service, upgrade_request("/forbidden"))
+        .await
+        .unwrap();
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert!(response.body.is_empty());
+
+    let service = Servio2HyperWebSocket::new(app, None, None);
+    let response = request(service, upgrade_request("/denied")).await.unwrap();
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.body, "denied");
+}
+
+/// Performs WebSocket handshake over raw connection, offering `permessage-deflate`. Returns
+/// negotiated extension.
+async fn deflate_handshake(
+    app: EchoWebSocket,
+    config: WebSocketConfig,
+    path: &str,
+) -> (DuplexStream, Option<String>) {
+    let (mut client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let service = Servio2HyperWebSocket::new(app, None, None).with_config(config);
+    tokio::spawn(serve_connection(server_io, service, HttpProtocol::Http1));
+
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\n\
+         Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\r\n"
+    );
+    client_io.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(client_io.read_u8().await.unwrap());
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"));
+    let extension = response.lines().find_map(|line| {
+        let (name, value) = line.split_once(": ")?;
+        name.eq_ignore_ascii_case("sec-websocket-extensions")
+            .then(|| value.to_owned())
+    });
+    (client_io, extension)
+}
+
+/// Sends compressed masked text frame.
+async fn send_compressed(io: &mut DuplexStream, compress: &mut Compress, text: &str) {
+    send_compressed_frame(io, compress, 0x80 | 0x40 | 0x1, text).await;
+}
+
+/// Sends masked frame with the first byte `head`, and `text` compressed as its payload.
+async fn send_compressed_frame(
+    io: &mut DuplexStream,
+    compress: &mut Compress,
+    head: u8,
+    text: &str,
+) {
+    let mut payload = Vec::with_capacity(text.len() + 64);
+    compress
+        .compress_vec(text.as_bytes(), &mut payload, FlushCompress::Sync)
+        .unwrap();
+    payload.truncate(payload.len() - 4);
+
+    let mask = [1, 2, 3, 4];
+    let mut frame = vec![head, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    io.write_all(&frame).await.unwrap();
+}
+
+/// Receives compressed text frame.
+async fn receive_compressed(io: &mut DuplexStream, decompress: &mut Decompress) -> String {
+    let head = io.read_u8().await.unwrap();
+    assert_eq!(head, 0x80 | 0x40 | 0x1);
+    let len = io.read_u8().await.unwrap();
+    let mut payload = vec![0; len as usize];
+    io.read_exact(&mut payload).await.unwrap();
+    payload.extend_from_slice(&[0, 0, 0xff, 0xff]);
+
+    let mut text = Vec::with_capacity(1024);
+    decompress
+        .decompress_vec(&payload, &mut text, FlushDecompress::Sync)
+        .unwrap();
+    String::from_utf8(text).unwrap()
+}
+
+#[tokio::test]
+async fn deflate() {
+    let (app, _) = echo();
+    let deflate = DeflateConfig::new()
+        .with_server_max_window_bits(10)
+        .with_client_max_window_bits(12);
+    let config = WebSocketConfig::new().with_deflate(deflate);
+    let (mut io, extension) = deflate_handshake(app, config, "/").await;
+    assert_eq!(
+        extension.as_deref(),
+        Some("permessage-deflate; server_max_window_bits=10; client_max_window_bits=12")
+    );
+
+    // Both sides keep compression context between messages
+    let mut compress = Compress::new_with_window_bits(Compression::default(), false, 12);
+    let mut decompress = Decompress::new_with_window_bits(false, 10);
+    for _ in 0..2 {
+        send_compressed(&mut io, &mut compress, "hello, hello, hello").await;
+        let text = receive_compressed(&mut io, &mut decompress).await;
+        assert_eq!(text, "hello, hello, hello");
+    }
+
+    // Accepted by the application
+    let (app, _) = echo();
+    let (mut io, extension) = deflate_handshake(app, WebSocketConfig::new(), "/deflate").await;
+    assert_eq!(
+        extension.as_deref(),
+        Some("permessage-deflate; server_no_context_takeover")
+    );
+
+    let mut compress = Compress::new(Compression::default(), false);
+    for _ in 0..2 {
+        let mut decompress = Decompress::new(false);
+        send_compressed(&mut io, &mut compress, "hello, hello, hello").await;
+        let text = receive_compressed(&mut io, &mut decompress).await;
+        assert_eq!(text, "hello, hello, hello");
+    }
+
+    // Not negotiated without configuration
+    let (app, _) = echo();
+    let (_, extension) = deflate_handshake(app, WebSocketConfig::new(), "/").await;
+    assert_eq!(extension, None);
+}
+
+#[tokio::test]
+async fn deflate_message_size() {
+    let (app, mut disconnects) = echo();
+    let config = WebSocketConfig::new()
+        .with_deflate(DeflateConfig::new())
+        .with_max_message_size(100);
+    let (mut io, _) = deflate_handshake(app, config, "/").await;
+
+    // The first fragment already inflates past the limit
+    let mut compress = Compress::new(Compression::default(), false);
+    let text = "a".repeat(1000);
+    send_compressed_frame(&mut io, &mut compress, 0x40 | 0x1, &text).await;
+    send_compressed_frame(&mut io, &mut compress, 0x80, "b").await;
+
+    let mut received = Vec::new();
+    io.read_to_end(&mut received).await.unwrap();
+    assert_eq!(disconnects.next().await, Some(1009));
+}
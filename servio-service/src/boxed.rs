@@ -0,0 +1,344 @@
+//! Type-erased services.
+//!
+//! [`Service`] has three associated types, so different services can't be stored in a single
+//! collection or returned from a function without boxing. Types in this module erase them into
+//! boxed `AppStream`, [`BoxError`] and boxed `Future`.
+
+use crate::{Event, Scope, Service};
+use futures_core::future::{BoxFuture, LocalBoxFuture};
+use futures_core::stream::{BoxStream, LocalBoxStream};
+use futures_core::Stream;
+use std::error::Error as StdError;
+use std::fmt;
+
+/// Type-erased error, returned by boxed services.
+///
+/// Unlike `Box<dyn Error>`, it implements [`std::error::Error`] and can be used as
+/// [`Service::Error`].
+pub struct BoxError(Box<dyn StdError + Send + Sync + 'static>);
+
+impl BoxError {
+    /// Wraps an error into `BoxError`.
+    #[inline]
+    pub fn new<E: StdError + Send + Sync + 'static>(error: E) -> Self {
+        Self(Box::new(error))
+    }
+
+    /// Returns the wrapped error.
+    #[inline]
+    pub fn into_inner(self) -> Box<dyn StdError + Send + Sync + 'static> {
+        self.0
+    }
+}
+
+impl From<Box<dyn StdError + Send + Sync + 'static>> for BoxError {
+    #[inline]
+    fn from(error: Box<dyn StdError + Send + Sync + 'static>) -> Self {
+        Self(error)
+    }
+}
+
+impl fmt::Debug for BoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for BoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl StdError for BoxError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
+type DynService<SS> = dyn Service<
+        SS,
+        AppStream = BoxStream<'static, Event>,
+        Error = BoxError,
+        Future = BoxFuture<'static, Result<BoxStream<'static, Event>, BoxError>>,
+    > + Send;
+
+type DynUnsyncService<SS> = dyn Service<
+    SS,
+    AppStream = LocalBoxStream<'static, Event>,
+    Error = BoxError,
+    Future = LocalBoxFuture<'static, Result<LocalBoxStream<'static, Event>, BoxError>>,
+>;
+
+/// Wraps a service, boxing its `AppStream`, `Error` and `Future`.
+struct Boxed<S> {
+    inner: S,
+}
+
+impl<S, SS> Service<SS> for Boxed<S>
+where
+    S: Service<SS>,
+    S::AppStream: Send + 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: Send + 'static,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let future = self.inner.call(scope, server_events);
+        Box::pin(async move {
+            match future.await {
+                Ok(app_stream) => Ok(Box::pin(app_stream) as Self::AppStream),
+                Err(e) => Err(BoxError::new(e)),
+            }
+        })
+    }
+}
+
+/// Same as [`Boxed`], but without `Send` requirement.
+struct UnsyncBoxed<S> {
+    inner: S,
+}
+
+impl<S, SS> Service<SS> for UnsyncBoxed<S>
+where
+    S: Service<SS>,
+    S::AppStream: 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: 'static,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = LocalBoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = LocalBoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let future = self.inner.call(scope, server_events);
+        Box::pin(async move {
+            match future.await {
+                Ok(app_stream) => Ok(Box::pin(app_stream) as Self::AppStream),
+                Err(e) => Err(BoxError::new(e)),
+            }
+        })
+    }
+}
+
+/// Type-erased `Send` service.
+pub struct BoxService<SS> {
+    inner: Box<DynService<SS>>,
+}
+
+impl<SS: Stream<Item = Event>> BoxService<SS> {
+    /// Creates a new `BoxService` from a service.
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<SS> + Send + 'static,
+        S::AppStream: Send + 'static,
+        S::Error: Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        Self {
+            inner: Box::new(Boxed { inner: service }),
+        }
+    }
+}
+
+impl<SS: Stream<Item = Event>> Service<SS> for BoxService<SS> {
+    type AppStream = BoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        self.inner.call(scope, server_events)
+    }
+}
+
+impl<SS> fmt::Debug for BoxService<SS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxService").finish_non_exhaustive()
+    }
+}
+
+/// Type-erased service without `Send` requirement.
+pub struct UnsyncBoxService<SS> {
+    inner: Box<DynUnsyncService<SS>>,
+}
+
+impl<SS: Stream<Item = Event>> UnsyncBoxService<SS> {
+    /// Creates a new `UnsyncBoxService` from a service.
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<SS> + 'static,
+        S::AppStream: 'static,
+        S::Error: Send + Sync + 'static,
+        S::Future: 'static,
+    {
+        Self {
+            inner: Box::new(UnsyncBoxed { inner: service }),
+        }
+    }
+}
+
+impl<SS: Stream<Item = Event>> Service<SS> for UnsyncBoxService<SS> {
+    type AppStream = LocalBoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = LocalBoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        self.inner.call(scope, server_events)
+    }
+}
+
+impl<SS> fmt::Debug for UnsyncBoxService<SS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnsyncBoxService").finish_non_exhaustive()
+    }
+}
+
+trait CloneService<SS: Stream<Item = Event>>:
+    Service<
+        SS,
+        AppStream = BoxStream<'static, Event>,
+        Error = BoxError,
+        Future = BoxFuture<'static, Result<BoxStream<'static, Event>, BoxError>>,
+    > + Send
+{
+    fn clone_box(&self) -> Box<dyn CloneService<SS>>;
+}
+
+impl<S, SS> CloneService<SS> for Boxed<S>
+where
+    S: Service<SS> + Clone + Send + 'static,
+    S::AppStream: Send + 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: Send + 'static,
+    SS: Stream<Item = Event>,
+{
+    fn clone_box(&self) -> Box<dyn CloneService<SS>> {
+        Box::new(Boxed {
+            inner: self.inner.clone(),
+        })
+    }
+}
+
+/// Type-erased `Send` + `Clone` service.
+pub struct BoxCloneService<SS> {
+    inner: Box<dyn CloneService<SS>>,
+}
+
+impl<SS: Stream<Item = Event>> BoxCloneService<SS> {
+    /// Creates a new `BoxCloneService` from a service.
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<SS> + Clone + Send + 'static,
+        S::AppStream: Send + 'static,
+        S::Error: Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        Self {
+            inner: Box::new(Boxed { inner: service }),
+        }
+    }
+}
+
+impl<SS: Stream<Item = Event>> Service<SS> for BoxCloneService<SS> {
+    type AppStream = BoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        self.inner.call(scope, server_events)
+    }
+}
+
+impl<SS: Stream<Item = Event>> Clone for BoxCloneService<SS> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone_box(),
+        }
+    }
+}
+
+impl<SS> fmt::Debug for BoxCloneService<SS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxCloneService").finish_non_exhaustive()
+    }
+}
+
+trait UnsyncCloneService<SS: Stream<Item = Event>>:
+    Service<
+    SS,
+    AppStream = LocalBoxStream<'static, Event>,
+    Error = BoxError,
+    Future = LocalBoxFuture<'static, Result<LocalBoxStream<'static, Event>, BoxError>>,
+>
+{
+    fn clone_box(&self) -> Box<dyn UnsyncCloneService<SS>>;
+}
+
+impl<S, SS> UnsyncCloneService<SS> for UnsyncBoxed<S>
+where
+    S: Service<SS> + Clone + 'static,
+    S::AppStream: 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: 'static,
+    SS: Stream<Item = Event>,
+{
+    fn clone_box(&self) -> Box<dyn UnsyncCloneService<SS>> {
+        Box::new(UnsyncBoxed {
+            inner: self.inner.clone(),
+        })
+    }
+}
+
+/// Type-erased `Clone` service without `Send` requirement.
+pub struct UnsyncBoxCloneService<SS> {
+    inner: Box<dyn UnsyncCloneService<SS>>,
+}
+
+impl<SS: Stream<Item = Event>> UnsyncBoxCloneService<SS> {
+    /// Creates a new `UnsyncBoxCloneService` from a service.
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<SS> + Clone + 'static,
+        S::AppStream: 'static,
+        S::Error: Send + Sync + 'static,
+        S::Future: 'static,
+    {
+        Self {
+            inner: Box::new(UnsyncBoxed { inner: service }),
+        }
+    }
+}
+
+impl<SS: Stream<Item = Event>> Service<SS> for UnsyncBoxCloneService<SS> {
+    type AppStream = LocalBoxStream<'static, Event>;
+    type Error = BoxError;
+    type Future = LocalBoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        self.inner.call(scope, server_events)
+    }
+}
+
+impl<SS: Stream<Item = Event>> Clone for UnsyncBoxCloneService<SS> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone_box(),
+        }
+    }
+}
+
+impl<SS> fmt::Debug for UnsyncBoxCloneService<SS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnsyncBoxCloneService")
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::layer::{layer_fn, Identity, Layer, LayerFn, Stack};
+
+/// Builder for composing multiple layers over a [`Service`](crate::Service).
+///
+/// Layers are applied in the order they are added: the first added layer becomes the outermost
+/// one. It means, that the first layer sees `Scope` and server events first, and application
+/// events last.
+///
+/// ```
+/// # use servio_service::{layer_fn, ServiceBuilder};
+/// # struct Logger<S>(S);
+/// # struct Auth<S>(S);
+/// # struct App;
+/// let service: Logger<Auth<App>> = ServiceBuilder::new()
+///     .layer(layer_fn(Logger))
+///     .layer(layer_fn(Auth))
+///     .service(App);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServiceBuilder<L> {
+    layer: L,
+}
+
+impl Default for ServiceBuilder<Identity> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceBuilder<Identity> {
+    /// Creates a new empty `ServiceBuilder`.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            layer: Identity::new(),
+        }
+    }
+}
+
+impl<L> ServiceBuilder<L> {
+    /// Adds a new layer. It will wrap all layers, that are added after it.
+    #[inline]
+    pub fn layer<T>(self, layer: T) -> ServiceBuilder<Stack<T, L>> {
+        ServiceBuilder {
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Adds an optional layer. If `layer` is `None`, it is skipped.
+    #[inline]
+    pub fn option_layer<T>(self, layer: Option<T>) -> ServiceBuilder<Stack<Option<T>, L>> {
+        self.layer(layer)
+    }
+
+    /// Adds a layer, created from a closure.
+    #[inline]
+    pub fn layer_fn<F>(self, f: F) -> ServiceBuilder<Stack<LayerFn<F>, L>> {
+        self.layer(layer_fn(f))
+    }
+
+    /// Returns the composed layer.
+    #[inline]
+    pub fn into_inner(self) -> L {
+        self.layer
+    }
+
+    /// Wraps the service with all layers of this builder.
+    #[inline]
+    pub fn service<S>(&self, service: S) -> L::Service
+    where
+        L: Layer<S>,
+    {
+        self.layer.layer(service)
+    }
+}
+
+impl<S, L> Layer<S> for ServiceBuilder<L>
+where
+    L: Layer<S>,
+{
+    type Service = L::Service;
+
+    #[inline]
+    fn layer(&self, inner: S) -> Self::Service {
+        self.layer.layer(inner)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{Event, Scope, Service};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Combines two different services into a single type.
+///
+/// Both services must share the same `Error` type. This is used by optional layers, but can
+/// also be useful to choose a service at runtime without boxing.
+#[derive(Clone, Copy, Debug)]
+pub enum Either<A, B> {
+    A(A),
+    B(B),
+}
+
+impl<A, B, SS> Service<SS> for Either<A, B>
+where
+    A: Service<SS>,
+    B: Service<SS, Error = A::Error>,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = EitherStream<A::AppStream, B::AppStream>;
+    type Error = A::Error;
+    type Future = EitherFuture<A::Future, B::Future>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        match self {
+            Either::A(inner) => EitherFuture::A {
+                inner: inner.call(scope, server_events),
+            },
+            Either::B(inner) => EitherFuture::B {
+                inner: inner.call(scope, server_events),
+            },
+        }
+    }
+}
+
+pin_project! {
+    /// Future, returned by [`Either`] service.
+    #[project = EitherFutureProj]
+    #[derive(Debug)]
+    pub enum EitherFuture<A, B> {
+        A { #[pin] inner: A },
+        B { #[pin] inner: B },
+    }
+}
+
+impl<A, B, TA, TB, E> Future for EitherFuture<A, B>
+where
+    A: Future<Output = Result<TA, E>>,
+    B: Future<Output = Result<TB, E>>,
+{
+    type Output = Result<EitherStream<TA, TB>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            EitherFutureProj::A { inner } => inner
+                .poll(cx)
+                .map(|r| r.map(|inner| EitherStream::A { inner })),
+            EitherFutureProj::B { inner } => inner
+                .poll(cx)
+                .map(|r| r.map(|inner| EitherStream::B { inner })),
+        }
+    }
+}
+
+pin_project! {
+    /// AppStream of [`Either`] service.
+    #[project = EitherStreamProj]
+    #[derive(Debug)]
+    pub enum EitherStream<A, B> {
+        A { #[pin] inner: A },
+        B { #[pin] inner: B },
+    }
+}
+
+impl<A, B> Stream for EitherStream<A, B>
+where
+    A: Stream,
+    B: Stream<Item = A::Item>,
+{
+    type Item = A::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.project() {
+            EitherStreamProj::A { inner } => inner.poll_next(cx),
+            EitherStreamProj::B { inner } => inner.poll_next(cx),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            EitherStream::A { inner } => inner.size_hint(),
+            EitherStream::B { inner } => inner.size_hint(),
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! Combinators for Servio services.
+//!
+//! Every combinator returns a concrete type, so they can be composed and named without boxing.
+
+use crate::{BoxCloneService, BoxService, Event, Scope, Service};
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Extension trait with combinators for any [`Service`].
+///
+/// Combinators carry `ServerStream` type as a parameter, so services, that are generic over
+/// server stream, can be wrapped without type annotations.
+pub trait ServiceExt<ServerStream: Stream<Item = Event>>: Service<ServerStream> {
+    /// Transforms `Scope` before it is passed to the service.
+    fn map_scope<F>(self, f: F) -> MapScope<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnMut(Scope) -> Scope,
+    {
+        MapScope::new(self, f)
+    }
+
+    /// Transforms each event, that is sent by server, before it reaches the service.
+    ///
+    /// The wrapped service receives server events as [`MapEvents`] stream. If it can't accept
+    /// `ServerStream` itself, use [`MapServerEvents::new`] instead.
+    fn map_server_events<F>(self, f: F) -> MapServerEvents<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnMut(Event) -> Event + Clone,
+    {
+        MapServerEvents::new(self, f)
+    }
+
+    /// Transforms each event, that is sent by the service to server.
+    fn map_app_events<F>(self, f: F) -> MapAppEvents<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnMut(Event) -> Event + Clone,
+    {
+        MapAppEvents::new(self, f)
+    }
+
+    /// Drops events, that are sent by the service to server, if predicate returns `false`.
+    fn filter_events<F>(self, f: F) -> FilterEvents<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnMut(&Event) -> bool + Clone,
+    {
+        FilterEvents::new(self, f)
+    }
+
+    /// Transforms error, returned by the service.
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnOnce(Self::Error) -> E + Clone,
+        E: std::error::Error,
+    {
+        MapErr::new(self, f)
+    }
+
+    /// Runs an asynchronous function after the service call completes, successfully or not.
+    ///
+    /// Function receives the result of the call, so it can be used to recover from errors or
+    /// to replace the `AppStream` completely.
+    fn then<F, Fut, AS, E>(self, f: F) -> Then<Self, F, ServerStream>
+    where
+        Self: Sized,
+        F: FnOnce(Result<Self::AppStream, Self::Error>) -> Fut + Clone,
+        Fut: Future<Output = Result<AS, E>>,
+        AS: Stream<Item = Event>,
+        E: std::error::Error,
+    {
+        Then::new(self, f)
+    }
+
+    /// Erases the type of the service, converting it into [`BoxService`].
+    fn boxed(self) -> BoxService<ServerStream>
+    where
+        Self: Sized + Send + 'static,
+        Self::AppStream: Send + 'static,
+        Self::Error: Send + Sync + 'static,
+        Self::Future: Send + 'static,
+    {
+        BoxService::new(self)
+    }
+
+    /// Erases the type of the service, converting it into [`BoxCloneService`].
+    fn boxed_clone(self) -> BoxCloneService<ServerStream>
+    where
+        Self: Sized + Clone + Send + 'static,
+        Self::AppStream: Send + 'static,
+        Self::Error: Send + Sync + 'static,
+        Self::Future: Send + 'static,
+    {
+        BoxCloneService::new(self)
+    }
+}
+
+impl<T, ServerStream> ServiceExt<ServerStream> for T
+where
+    T: Service<ServerStream> + ?Sized,
+    ServerStream: Stream<Item = Event>,
+{
+}
+
+/// Implements constructor, `Clone` and `Debug` for a combinator service. They are implemented by
+/// hand, because `ServerStream` is only a marker and should not be required to implement them.
+macro_rules! combinator {
+    ($name:ident) => {
+        impl<S, F, SS> $name<S, F, SS> {
+            /// Wraps the service with this combinator.
+            #[inline]
+            pub fn new(inner: S, f: F) -> Self {
+                Self {
+                    inner,
+                    f,
+                    _stream: PhantomData,
+                }
+            }
+        }
+
+        impl<S: Clone, F: Clone, SS> Clone for $name<S, F, SS> {
+            fn clone(&self) -> Self {
+                Self::new(self.inner.clone(), self.f.clone())
+            }
+        }
+
+        impl<S: fmt::Debug, F, SS> fmt::Debug for $name<S, F, SS> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("inner", &self.inner)
+                    .finish_non_exhaustive()
+            }
+        }
+    };
+}
+
+/// Service for the [`map_scope`](ServiceExt::map_scope) combinator.
+pub struct MapScope<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(MapScope);
+
+impl<S, F, SS> Service<SS> for MapScope<S, F, SS>
+where
+    S: Service<SS>,
+    F: FnMut(Scope) -> Scope,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = S::AppStream;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        self.inner.call((self.f)(scope), server_events)
+    }
+}
+
+/// Service for the [`map_server_events`](ServiceExt::map_server_events) combinator.
+pub struct MapServerEvents<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(MapServerEvents);
+
+impl<S, F, SS> Service<SS> for MapServerEvents<S, F, SS>
+where
+    S: Service<MapEvents<SS, F>>,
+    F: FnMut(Event) -> Event + Clone,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = S::AppStream;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let server_events = MapEvents {
+            stream: server_events,
+            f: self.f.clone(),
+        };
+        self.inner.call(scope, server_events)
+    }
+}
+
+/// Service for the [`map_app_events`](ServiceExt::map_app_events) combinator.
+pub struct MapAppEvents<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(MapAppEvents);
+
+impl<S, F, SS> Service<SS> for MapAppEvents<S, F, SS>
+where
+    S: Service<SS>,
+    F: FnMut(Event) -> Event + Clone,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = MapEvents<S::AppStream, F>;
+    type Error = S::Error;
+    type Future = MapAppEventsFuture<S::Future, F>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        MapAppEventsFuture {
+            future: self.inner.call(scope, server_events),
+            f: Some(self.f.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for the [`map_app_events`](ServiceExt::map_app_events) combinator.
+    #[derive(Debug)]
+    pub struct MapAppEventsFuture<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F, AS, E> Future for MapAppEventsFuture<Fut, F>
+where
+    Fut: Future<Output = Result<AS, E>>,
+{
+    type Output = Result<MapEvents<AS, F>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("polled after completion");
+        Poll::Ready(result.map(|stream| MapEvents { stream, f }))
+    }
+}
+
+pin_project! {
+    /// Stream, that transforms each event with a function.
+    #[derive(Debug)]
+    pub struct MapEvents<St, F> {
+        #[pin]
+        stream: St,
+        f: F,
+    }
+}
+
+impl<St, F> Stream for MapEvents<St, F>
+where
+    St: Stream<Item = Event>,
+    F: FnMut(Event) -> Event,
+{
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let event = ready!(this.stream.poll_next(cx));
+        Poll::Ready(event.map(this.f))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Service for the [`filter_events`](ServiceExt::filter_events) combinator.
+pub struct FilterEvents<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(FilterEvents);
+
+impl<S, F, SS> Service<SS> for FilterEvents<S, F, SS>
+where
+    S: Service<SS>,
+    F: FnMut(&Event) -> bool + Clone,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = FilterEventStream<S::AppStream, F>;
+    type Error = S::Error;
+    type Future = FilterEventsFuture<S::Future, F>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        FilterEventsFuture {
+            future: self.inner.call(scope, server_events),
+            f: Some(self.f.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for the [`filter_events`](ServiceExt::filter_events) combinator.
+    #[derive(Debug)]
+    pub struct FilterEventsFuture<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F, AS, E> Future for FilterEventsFuture<Fut, F>
+where
+    Fut: Future<Output = Result<AS, E>>,
+{
+    type Output = Result<FilterEventStream<AS, F>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("polled after completion");
+        Poll::Ready(result.map(|stream| FilterEventStream { stream, f }))
+    }
+}
+
+pin_project! {
+    /// Stream, that skips events, not matching a predicate.
+    #[derive(Debug)]
+    pub struct FilterEventStream<St, F> {
+        #[pin]
+        stream: St,
+        f: F,
+    }
+}
+
+impl<St, F> Stream for FilterEventStream<St, F>
+where
+    St: Stream<Item = Event>,
+    F: FnMut(&Event) -> bool,
+{
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(event) if !(this.f)(&event) => continue,
+                event => return Poll::Ready(event),
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.stream.size_hint().1)
+    }
+}
+
+/// Service for the [`map_err`](ServiceExt::map_err) combinator.
+pub struct MapErr<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(MapErr);
+
+impl<S, F, E, SS> Service<SS> for MapErr<S, F, SS>
+where
+    S: Service<SS>,
+    F: FnOnce(S::Error) -> E + Clone,
+    E: std::error::Error,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = S::AppStream;
+    type Error = E;
+    type Future = MapErrFuture<S::Future, F>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        MapErrFuture {
+            future: self.inner.call(scope, server_events),
+            f: Some(self.f.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for the [`map_err`](ServiceExt::map_err) combinator.
+    #[derive(Debug)]
+    pub struct MapErrFuture<Fut, F> {
+        #[pin]
+        future: Fut,
+        f: Option<F>,
+    }
+}
+
+impl<Fut, F, AS, E1, E2> Future for MapErrFuture<Fut, F>
+where
+    Fut: Future<Output = Result<AS, E1>>,
+    F: FnOnce(E1) -> E2,
+{
+    type Output = Result<AS, E2>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.future.poll(cx));
+        let f = this.f.take().expect("polled after completion");
+        Poll::Ready(result.map_err(f))
+    }
+}
+
+/// Service for the [`then`](ServiceExt::then) combinator.
+pub struct Then<S, F, SS> {
+    inner: S,
+    f: F,
+    _stream: PhantomData<fn(SS)>,
+}
+
+combinator!(Then);
+
+impl<S, F, Fut, AS, E, SS> Service<SS> for Then<S, F, SS>
+where
+    S: Service<SS>,
+    F: FnOnce(Result<S::AppStream, S::Error>) -> Fut + Clone,
+    Fut: Future<Output = Result<AS, E>>,
+    AS: Stream<Item = Event>,
+    E: std::error::Error,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = AS;
+    type Error = E;
+    type Future = ThenFuture<S::Future, Fut, F>;
+
+    #[inline]
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        ThenFuture {
+            first: self.inner.call(scope, server_events),
+            second: None,
+            f: Some(self.f.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Future for the [`then`](ServiceExt::then) combinator.
+    #[derive(Debug)]
+    pub struct ThenFuture<Fut1, Fut2, F> {
+        #[pin]
+        first: Fut1,
+        #[pin]
+        second: Option<Fut2>,
+        f: Option<F>,
+    }
+}
+
+impl<Fut1, Fut2, F> Future for ThenFuture<Fut1, Fut2, F>
+where
+    Fut1: Future,
+    Fut2: Future,
+    F: FnOnce(Fut1::Output) -> Fut2,
+{
+    type Output = Fut2::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if this.second.is_none() {
+            let result = ready!(this.first.poll(cx));
+            let f = this.f.take().expect("polled after completion");
+            this.second.set(Some(f(result)));
+        }
+
+        let second = this.second.as_pin_mut().expect("second future is set");
+        second.poll(cx)
+    }
+}
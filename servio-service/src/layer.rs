@@ -0,0 +1,126 @@
+use crate::Either;
+use std::fmt;
+
+/// Decorates a [`Service`](crate::Service), transforming it into another `Service`.
+///
+/// Layers are the building blocks of Servio middleware. A layer takes an inner service and wraps
+/// it, so the resulting service can transform `Scope` and events on their way between server and
+/// application.
+pub trait Layer<S> {
+    /// The wrapped service.
+    type Service;
+
+    /// Wraps the given service with this layer.
+    fn layer(&self, inner: S) -> Self::Service;
+}
+
+impl<L, S> Layer<S> for &L
+where
+    L: Layer<S> + ?Sized,
+{
+    type Service = L::Service;
+
+    #[inline]
+    fn layer(&self, inner: S) -> Self::Service {
+        (**self).layer(inner)
+    }
+}
+
+/// A no-op layer, that returns inner service unchanged.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Identity {
+    _p: (),
+}
+
+impl Identity {
+    /// Creates a new `Identity` layer.
+    #[inline]
+    pub fn new() -> Self {
+        Self { _p: () }
+    }
+}
+
+impl<S> Layer<S> for Identity {
+    type Service = S;
+
+    #[inline]
+    fn layer(&self, inner: S) -> Self::Service {
+        inner
+    }
+}
+
+/// Two layers, chained together. `Outer` wraps the service, produced by `Inner`.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Creates a new `Stack` from inner and outer layers.
+    #[inline]
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<S, Inner, Outer> Layer<S> for Stack<Inner, Outer>
+where
+    Inner: Layer<S>,
+    Outer: Layer<Inner::Service>,
+{
+    type Service = Outer::Service;
+
+    #[inline]
+    fn layer(&self, service: S) -> Self::Service {
+        self.outer.layer(self.inner.layer(service))
+    }
+}
+
+/// Layer, created from a closure. See [`layer_fn`].
+#[derive(Clone, Copy)]
+pub struct LayerFn<F> {
+    f: F,
+}
+
+/// Returns a new [`LayerFn`], that calls `f` to wrap inner service.
+#[inline]
+pub fn layer_fn<F>(f: F) -> LayerFn<F> {
+    LayerFn { f }
+}
+
+impl<F, S, Out> Layer<S> for LayerFn<F>
+where
+    F: Fn(S) -> Out,
+{
+    type Service = Out;
+
+    #[inline]
+    fn layer(&self, inner: S) -> Self::Service {
+        (self.f)(inner)
+    }
+}
+
+impl<F> fmt::Debug for LayerFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayerFn")
+            .field("f", &std::any::type_name::<F>())
+            .finish()
+    }
+}
+
+/// Optional layer. If layer is `None`, inner service is passed through unchanged.
+impl<L, S> Layer<S> for Option<L>
+where
+    L: Layer<S>,
+{
+    type Service = Either<L::Service, S>;
+
+    #[inline]
+    fn layer(&self, inner: S) -> Self::Service {
+        match self {
+            Some(layer) => Either::A(layer.layer(inner)),
+            None => Either::B(inner),
+        }
+    }
+}
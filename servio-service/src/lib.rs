@@ -1,5 +1,21 @@
 #![forbid(unsafe_code)]
 
+pub mod boxed;
+mod builder;
+mod either;
+pub mod ext;
+mod layer;
+mod make;
+mod ready;
+
+pub use boxed::{BoxCloneService, BoxError, BoxService, UnsyncBoxCloneService, UnsyncBoxService};
+pub use builder::ServiceBuilder;
+pub use either::{Either, EitherFuture, EitherStream};
+pub use ext::ServiceExt;
+pub use layer::{layer_fn, Identity, Layer, LayerFn, Stack};
+pub use make::MakeService;
+pub use ready::{ready, Ready, ReadyService};
+
 use futures_core::Stream;
 use std::any::{Any, TypeId};
 use std::borrow::Cow;
@@ -128,6 +144,34 @@ impl Event {
     pub fn get_ref<T: Any + Sync + Send>(&self) -> Option<&T> {
         self.event.downcast_ref::<T>()
     }
+
+    /// Creates new event of specified family. Family name and payload type are taken from
+    /// [`EventFamily`], so they always match.
+    #[inline]
+    pub fn typed<F: EventFamily>(payload: F::Payload) -> Self {
+        Self::new(F::NAME.into(), payload)
+    }
+
+    /// Returns reference to event payload, if event belongs to specified family.
+    #[inline]
+    pub fn downcast<F: EventFamily>(&self) -> Option<&F::Payload> {
+        if self.family != F::NAME {
+            return None;
+        }
+        self.get_ref::<F::Payload>()
+    }
+}
+
+/// Event family, binding family name to payload type.
+///
+/// Families are usually implemented on uninhabited marker types, defined alongside the payload.
+/// Events, created with [`Event::typed`] and read with [`Event::downcast`], can never have family
+/// name and payload type disagree.
+pub trait EventFamily {
+    /// Family name, as returned by [`Event::family`].
+    const NAME: &'static str;
+    /// Payload type of events of this family.
+    type Payload: Any + Sync + Send;
 }
 
 /// Trait, representing a Service, that is used to handle connections.
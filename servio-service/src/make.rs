@@ -0,0 +1,36 @@
+use crate::{Event, Service};
+use futures_core::Stream;
+use std::convert::Infallible;
+use std::future::{ready, Future, Ready};
+
+/// Factory of services, that creates a new [`Service`] for each connection.
+///
+/// `Target` is connection-level information, provided by server, like peer address or TLS
+/// details. It allows to parameterize the service with the connection state.
+///
+/// Every `Clone` service is a `MakeService`, that returns its clone for any target. A type, that
+/// is both `Service` and `Clone`, gets this implementation and can't provide its own. Factories,
+/// that aren't services, may implement `Clone` freely.
+pub trait MakeService<Target, ServerStream: Stream<Item = Event>> {
+    type Service: Service<ServerStream>;
+    type MakeError: std::error::Error;
+    type Future: Future<Output = Result<Self::Service, Self::MakeError>>;
+
+    /// Creates a new service for the connection, described by `target`.
+    fn make_service(&mut self, target: Target) -> Self::Future;
+}
+
+impl<S, Target, ServerStream> MakeService<Target, ServerStream> for S
+where
+    S: Service<ServerStream> + Clone,
+    ServerStream: Stream<Item = Event>,
+{
+    type Service = S;
+    type MakeError = Infallible;
+    type Future = Ready<Result<S, Infallible>>;
+
+    #[inline]
+    fn make_service(&mut self, _target: Target) -> Self::Future {
+        ready(Ok(self.clone()))
+    }
+}
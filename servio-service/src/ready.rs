@@ -0,0 +1,58 @@
+use crate::{Event, Service};
+use futures_core::Stream;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Service, that can signal whether it is able to accept new connections.
+///
+/// Readiness is opt-in: servers that support it, wait for [`poll_ready`](Self::poll_ready) to
+/// return `Ready(Ok(()))` before every [`call`](Service::call). A saturated service returns
+/// `Pending` and wakes the task, when it is able to accept work again, so server stops
+/// dispatching requests until then.
+///
+/// Once `poll_ready` returned `Ready(Ok(()))`, the next `call` is allowed to rely on reserved
+/// capacity. Calling `call` without readiness is a contract violation and may panic.
+pub trait ReadyService<ServerStream: Stream<Item = Event>>: Service<ServerStream> {
+    /// Returns `Ready(Ok(()))` when the service is able to process the next connection.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
+}
+
+/// Returns a future, that resolves when the service is ready to accept a connection.
+pub fn ready<S, ServerStream>(service: &mut S) -> Ready<'_, S, ServerStream>
+where
+    S: ReadyService<ServerStream>,
+    ServerStream: Stream<Item = Event>,
+{
+    Ready {
+        service,
+        _stream: PhantomData,
+    }
+}
+
+/// Future for the [`ready`] function.
+pub struct Ready<'a, S, ServerStream> {
+    service: &'a mut S,
+    _stream: PhantomData<fn(ServerStream)>,
+}
+
+impl<'a, S, ServerStream> Future for Ready<'a, S, ServerStream>
+where
+    S: ReadyService<ServerStream>,
+    ServerStream: Stream<Item = Event>,
+{
+    type Output = Result<(), S::Error>;
+
+    #[inline]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.service.poll_ready(cx)
+    }
+}
+
+impl<'a, S, ServerStream> fmt::Debug for Ready<'a, S, ServerStream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ready").finish_non_exhaustive()
+    }
+}
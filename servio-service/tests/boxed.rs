@@ -0,0 +1,122 @@
+use futures_core::stream::BoxStream;
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, StreamExt};
+use servio_service::{
+    BoxCloneService, BoxService, Event, Scope, Service, ServiceExt, UnsyncBoxCloneService,
+};
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::fmt;
+use std::rc::Rc;
+
+const EVENT_TEST: &str = "test";
+
+#[derive(Debug)]
+struct AppError;
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("app error")
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Responds with a single static event.
+#[derive(Clone)]
+struct Respond(&'static str);
+
+impl<SS: Stream<Item = Event>> Service<SS> for Respond {
+    type AppStream = stream::Once<Ready<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        let event = Event::new(EVENT_TEST.into(), self.0);
+        future::ok(stream::once(future::ready(event)))
+    }
+}
+
+/// Always fails.
+#[derive(Clone)]
+struct Fail;
+
+impl<SS: Stream<Item = Event>> Service<SS> for Fail {
+    type AppStream = stream::Empty<Event>;
+    type Error = AppError;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        future::err(AppError)
+    }
+}
+
+/// `!Send` service, counting its calls.
+#[derive(Clone)]
+struct Local(Rc<Cell<usize>>);
+
+impl<SS: Stream<Item = Event>> Service<SS> for Local {
+    type AppStream = stream::Once<Ready<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        self.0.set(self.0.get() + 1);
+        let event = Event::new(EVENT_TEST.into(), "local");
+        future::ok(stream::once(future::ready(event)))
+    }
+}
+
+type ServerStream = BoxStream<'static, Event>;
+
+fn call<S>(service: &mut S) -> Result<Vec<&'static str>, String>
+where
+    S: Service<ServerStream>,
+{
+    let scope = Scope::new("test".into());
+    let app_stream =
+        block_on(service.call(scope, stream::empty().boxed())).map_err(|e| e.to_string())?;
+    let events = block_on(app_stream.collect::<Vec<_>>());
+    Ok(events
+        .iter()
+        .map(|e| *e.get_ref::<&'static str>().unwrap())
+        .collect())
+}
+
+#[test]
+fn heterogeneous_services() {
+    let mut services: Vec<BoxService<ServerStream>> = vec![
+        BoxService::new(Respond("a")),
+        Fail.boxed(),
+        Respond("b").boxed(),
+    ];
+
+    let results: Vec<_> = services.iter_mut().map(call).collect();
+    assert_eq!(
+        results,
+        [Ok(vec!["a"]), Err("app error".to_string()), Ok(vec!["b"])]
+    );
+}
+
+#[test]
+fn clone_service() {
+    let service: BoxCloneService<ServerStream> = Respond("a").boxed_clone();
+    let mut services = vec![service.clone(), service];
+
+    for service in &mut services {
+        assert_eq!(call(service), Ok(vec!["a"]));
+    }
+}
+
+#[test]
+fn unsync_clone_service() {
+    let calls = Rc::new(Cell::new(0));
+    let mut service = UnsyncBoxCloneService::<ServerStream>::new(Local(calls.clone()));
+    let mut cloned = service.clone();
+
+    assert_eq!(call(&mut service), Ok(vec!["local"]));
+    assert_eq!(call(&mut cloned), Ok(vec!["local"]));
+    assert_eq!(calls.get(), 2);
+}
@@ -0,0 +1,34 @@
+use servio_service::{Event, EventFamily};
+
+enum Greeting {}
+
+impl EventFamily for Greeting {
+    const NAME: &'static str = "greeting";
+    type Payload = String;
+}
+
+enum Farewell {}
+
+impl EventFamily for Farewell {
+    const NAME: &'static str = "farewell";
+    type Payload = String;
+}
+
+#[test]
+fn typed_event() {
+    let event = Event::typed::<Greeting>("hello".into());
+    assert_eq!(event.family(), "greeting");
+    assert_eq!(event.downcast::<Greeting>().unwrap(), "hello");
+    assert_eq!(event.get_ref::<String>().unwrap(), "hello");
+}
+
+#[test]
+fn family_mismatch() {
+    // Same payload type, but different family
+    let event = Event::typed::<Farewell>("bye".into());
+    assert!(event.downcast::<Greeting>().is_none());
+
+    // Family matches, but payload doesn't
+    let event = Event::new("greeting".into(), 42u32);
+    assert!(event.downcast::<Greeting>().is_none());
+}
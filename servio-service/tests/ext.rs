@@ -0,0 +1,115 @@
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, StreamExt};
+use servio_service::{Event, Scope, Service, ServiceExt};
+use std::fmt;
+
+const EVENT_TEST: &str = "test";
+
+#[derive(Debug, PartialEq)]
+struct AppError(&'static str);
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+#[derive(Debug, PartialEq)]
+struct WrappedError(String);
+
+impl fmt::Display for WrappedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for WrappedError {}
+
+fn event(payload: impl Into<String>) -> Event {
+    Event::new(EVENT_TEST.into(), payload.into())
+}
+
+fn payloads(events: Vec<Event>) -> Vec<String> {
+    events
+        .iter()
+        .map(|e| e.get_ref::<String>().unwrap().clone())
+        .collect()
+}
+
+/// Application, that responds with protocol identifier followed by all server events.
+/// Fails on "fail" protocol.
+#[derive(Clone)]
+struct Echo;
+
+impl<SS> Service<SS> for Echo
+where
+    SS: Stream<Item = Event> + Unpin,
+{
+    type AppStream = stream::Iter<std::vec::IntoIter<Event>>;
+    type Error = AppError;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        if scope.protocol() == "fail" {
+            return future::err(AppError("failed"));
+        }
+
+        let mut events = vec![event(scope.protocol())];
+        events.extend(block_on(server_events.collect::<Vec<_>>()));
+        future::ok(stream::iter(events))
+    }
+}
+
+fn run<S>(mut service: S, protocol: &'static str) -> Result<Vec<String>, S::Error>
+where
+    S: Service<stream::Iter<std::vec::IntoIter<Event>>>,
+{
+    let server_events = stream::iter(vec![event("1"), event("2")]);
+    let app_stream = block_on(service.call(Scope::new(protocol.into()), server_events))?;
+    Ok(payloads(block_on(app_stream.collect())))
+}
+
+#[test]
+fn map_scope() {
+    let service = Echo.map_scope(|scope| scope.with_protocol("mapped".into()));
+    assert_eq!(run(service, "test").unwrap(), ["mapped", "1", "2"]);
+}
+
+#[test]
+fn map_events() {
+    let service = Echo
+        .map_server_events(|e| event(format!("s{}", e.get_ref::<String>().unwrap())))
+        .map_app_events(|e| event(format!("a{}", e.get_ref::<String>().unwrap())));
+    assert_eq!(run(service, "test").unwrap(), ["atest", "as1", "as2"]);
+}
+
+#[test]
+fn filter_events() {
+    let service = Echo.filter_events(|e| e.get_ref::<String>().unwrap() != "1");
+    assert_eq!(run(service, "test").unwrap(), ["test", "2"]);
+}
+
+#[test]
+fn map_err() {
+    let service = Echo.map_err(|e| WrappedError(format!("wrapped: {e}")));
+    assert_eq!(
+        run(service, "fail").unwrap_err(),
+        WrappedError("wrapped: failed".into())
+    );
+}
+
+#[test]
+fn then_recovers_error() {
+    let service = Echo.then(|result| async move {
+        match result {
+            Ok(stream) => Ok::<_, AppError>(stream),
+            Err(e) => Ok(stream::iter(vec![event(e.0)])),
+        }
+    });
+    assert_eq!(run(service.clone(), "fail").unwrap(), ["failed"]);
+    assert_eq!(run(service, "test").unwrap(), ["test", "1", "2"]);
+}
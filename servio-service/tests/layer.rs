@@ -0,0 +1,127 @@
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{BoxFuture, FutureExt, TryFutureExt};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use servio_service::{layer_fn, Event, Identity, Layer, Scope, Service, ServiceBuilder};
+use std::convert::Infallible;
+
+const EVENT_TEST: &str = "test";
+
+#[derive(Clone, Debug, Default)]
+struct Trace(Vec<&'static str>);
+
+/// Appends its name to `Trace` scope and to every string event, produced by application.
+#[derive(Clone)]
+struct Mark<S> {
+    name: &'static str,
+    inner: S,
+}
+
+fn mark<S>(name: &'static str) -> impl Layer<S, Service = Mark<S>> + Clone {
+    layer_fn(move |inner| Mark { name, inner })
+}
+
+impl<S, SS> Service<SS> for Mark<S>
+where
+    S: Service<SS, Error = Infallible>,
+    S::AppStream: Send + 'static,
+    S::Future: Send + 'static,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, mut scope: Scope, server_events: SS) -> Self::Future {
+        let mut trace = scope
+            .remove::<Trace>()
+            .map(|t| (*t).clone())
+            .unwrap_or_default();
+        trace.0.push(self.name);
+        scope.insert(trace);
+
+        let name = self.name;
+        self.inner
+            .call(scope, server_events)
+            .map_ok(move |app_stream| {
+                app_stream
+                    .map(move |event| {
+                        let payload = event.get_ref::<String>().unwrap();
+                        Event::new(EVENT_TEST.into(), format!("{payload}{name}"))
+                    })
+                    .boxed()
+            })
+            .boxed()
+    }
+}
+
+/// Application, that responds with a single event, containing `Trace` scope.
+#[derive(Clone)]
+struct App;
+
+impl<SS: Stream<Item = Event>> Service<SS> for App {
+    type AppStream = stream::Iter<std::vec::IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = futures_util::future::Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, _server_events: SS) -> Self::Future {
+        let trace = scope.get::<Trace>().unwrap_or_default();
+        let payload = format!("{}|", trace.0.join(","));
+        futures_util::future::ok(stream::iter(vec![Event::new(EVENT_TEST.into(), payload)]))
+    }
+}
+
+fn run<S>(mut service: S) -> String
+where
+    S: Service<stream::Empty<Event>>,
+    S::Error: std::fmt::Debug,
+{
+    let scope = Scope::new("test".into());
+    let app_stream = block_on(service.call(scope, stream::empty())).unwrap();
+    let events = block_on(app_stream.collect::<Vec<_>>());
+    assert_eq!(events.len(), 1);
+    events[0].get_ref::<String>().unwrap().clone()
+}
+
+#[test]
+fn identity_layer() {
+    let service = Identity::new().layer(App);
+    assert_eq!(run(service), "|");
+}
+
+#[test]
+fn builder_layer_order() {
+    let service = ServiceBuilder::new()
+        .layer(mark("a"))
+        .layer(mark("b"))
+        .layer(mark("c"))
+        .service(App);
+
+    // Scope flows from the outermost layer to application, events flow back.
+    assert_eq!(run(service), "a,b,c|cba");
+}
+
+#[test]
+fn builder_as_layer() {
+    let inner = ServiceBuilder::new().layer(mark("b")).layer(mark("c"));
+    let service = ServiceBuilder::new()
+        .layer(mark("a"))
+        .layer(inner)
+        .layer(mark("d"))
+        .service(App);
+
+    assert_eq!(run(service), "a,b,c,d|dcba");
+}
+
+#[test]
+fn builder_option_layer() {
+    let builder = |enabled: bool| {
+        ServiceBuilder::new()
+            .layer(mark("a"))
+            .option_layer(enabled.then(|| mark("b")))
+            .layer(mark("c"))
+    };
+
+    assert_eq!(run(builder(true).service(App)), "a,b,c|cba");
+    assert_eq!(run(builder(false).service(App)), "a,c|ca");
+}
@@ -0,0 +1,38 @@
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, StreamExt};
+use servio_service::{Event, MakeService, Scope, Service};
+use std::convert::Infallible;
+
+const EVENT_TEST: &str = "test";
+
+/// Responds with a single static event.
+#[derive(Clone)]
+struct Respond(&'static str);
+
+impl<SS: Stream<Item = Event>> Service<SS> for Respond {
+    type AppStream = stream::Once<Ready<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        let event = Event::new(EVENT_TEST.into(), self.0);
+        future::ok(stream::once(future::ready(event)))
+    }
+}
+
+#[test]
+fn clone_service_is_factory() {
+    let mut factory = Respond("hello");
+    let mut service = block_on(MakeService::<_, stream::Empty<Event>>::make_service(
+        &mut factory,
+        "target",
+    ))
+    .unwrap();
+
+    let app_stream = block_on(service.call(Scope::new("test".into()), stream::empty())).unwrap();
+    let events = block_on(app_stream.collect::<Vec<_>>());
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].get_ref::<&str>(), Some(&"hello"));
+}
@@ -0,0 +1,46 @@
+//! Bridge between Servio services and [`tower`](https://docs.rs/tower) services.
+//!
+//! [`Tower2Servio`] turns a tower `Service<http::Request<B>>` into a Servio service for the `http`
+//! protocol, so existing tower stacks can be mounted into Servio applications. [`Servio2Tower`]
+//! does the opposite, so tower middleware (timeouts, tracing, limits) can wrap Servio services.
+//! [`ReadyServio2Tower`] also forwards readiness of Servio services to tower.
+#![forbid(unsafe_code)]
+
+mod servio2tower;
+mod tower2servio;
+
+pub use servio2tower::{ReadyServio2Tower, RequestStream, ResponseBody, Servio2Tower};
+pub use tower2servio::{RequestBody, ResponseStream, Tower2Servio};
+
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt;
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Error, returned when Servio side of the bridge does not follow HTTP protocol.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum ProtocolError {
+    /// Scope protocol is not `http`.
+    UnsupportedProtocol(Cow<'static, str>),
+    /// Scope does not contain `HttpScope`.
+    MissingHttpScope,
+    /// App stream ended before `ResponseStart` was sent.
+    UnexpectedEnd,
+    /// App stream sent an HTTP event other than `ResponseStart` to start a response.
+    UnexpectedEvent(Cow<'static, str>),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedProtocol(protocol) => write!(f, "unsupported protocol: {protocol}"),
+            Self::MissingHttpScope => f.write_str("scope does not contain HttpScope"),
+            Self::UnexpectedEnd => f.write_str("app stream ended before ResponseStart"),
+            Self::UnexpectedEvent(event) => write!(f, "expected ResponseStart, got {event}"),
+        }
+    }
+}
+
+impl StdError for ProtocolError {}
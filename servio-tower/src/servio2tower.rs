@@ -0,0 +1,318 @@
+use crate::{BoxError, ProtocolError};
+use bytes::{Buf, Bytes};
+use futures_core::future::BoxFuture;
+use futures_core::Stream;
+use futures_util::{FutureExt, StreamExt};
+use http::{HeaderMap, Request, Response};
+use http_body::Body as HttpBody;
+use pin_project_lite::pin_project;
+use servio_http::http::{
+    Disconnect, HttpEvent, HttpFamily, HttpScope, RequestChunk, RequestTrailer, ResponseChunk,
+    ResponseStart, ResponseTrailer, PROTOCOL_HTTP,
+};
+use servio_service::{Event, ReadyService, Scope, Service};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tower_service::Service as TowerService;
+
+/// Servio to tower service wrapper. It can be used to transform Servio-compatible service into
+/// `tower`-compatible HTTP service, so it can be wrapped with tower middleware.
+///
+/// Request is passed to the Servio service as `http` protocol scope with `RequestChunk` events.
+/// The wrapper is always ready. Services, that report readiness with [`ReadyService`], should be
+/// wrapped with [`ReadyServio2Tower`] instead.
+#[derive(Clone, Debug)]
+pub struct Servio2Tower<S> {
+    inner: S,
+}
+
+impl<S> Servio2Tower<S> {
+    pub fn new(service: S) -> Self {
+        Self { inner: service }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> TowerService<Request<B>> for Servio2Tower<S>
+where
+    S: Service<RequestStream<B>>,
+    S::AppStream: Send + Unpin + 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: Send + 'static,
+    B: HttpBody,
+{
+    type Response = Response<ResponseBody<S::AppStream>>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        call_service(&mut self.inner, req)
+    }
+}
+
+/// Servio to tower service wrapper, that forwards readiness of [`ReadyService`] to tower, so
+/// tower middleware and servers get backpressure of the Servio service.
+#[derive(Clone, Debug)]
+pub struct ReadyServio2Tower<S> {
+    inner: S,
+}
+
+impl<S> ReadyServio2Tower<S> {
+    pub fn new(service: S) -> Self {
+        Self { inner: service }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> TowerService<Request<B>> for ReadyServio2Tower<S>
+where
+    S: ReadyService<RequestStream<B>>,
+    S::AppStream: Send + Unpin + 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: Send + 'static,
+    B: HttpBody,
+{
+    type Response = Response<ResponseBody<S::AppStream>>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        call_service(&mut self.inner, req)
+    }
+}
+
+/// Calls the Servio service with the request and builds response from its app stream.
+fn call_service<S, B>(
+    service: &mut S,
+    req: Request<B>,
+) -> BoxFuture<'static, Result<Response<ResponseBody<S::AppStream>>, BoxError>>
+where
+    S: Service<RequestStream<B>>,
+    S::AppStream: Send + Unpin + 'static,
+    S::Error: Send + Sync + 'static,
+    S::Future: Send + 'static,
+    B: HttpBody,
+{
+    let (parts, body) = req.into_parts();
+
+    let mut http_scope = HttpScope::default();
+    http_scope.method = parts.method;
+    http_scope.set_uri(parts.uri);
+    http_scope.version = parts.version;
+    http_scope.headers = parts.headers;
+
+    let scope = Scope::new(PROTOCOL_HTTP.into()).with_scope(http_scope);
+
+    let app_stream_fut = service.call(scope, RequestStream::new(body));
+    async move { build_response(app_stream_fut.await?).await }.boxed()
+}
+
+async fn build_response<AS>(mut app_stream: AS) -> Result<Response<ResponseBody<AS>>, BoxError>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    let start = loop {
+        let Some(event) = app_stream.next().await else {
+            return Err(ProtocolError::UnexpectedEnd.into());
+        };
+        match event.downcast::<HttpFamily>() {
+            Some(HttpEvent::ResponseStart(start)) => break start.clone(),
+            Some(event) => {
+                return Err(ProtocolError::UnexpectedEvent(format!("{event:?}").into()).into())
+            }
+            None => continue,
+        }
+    };
+
+    let ResponseStart {
+        status,
+        headers,
+        trailers,
+        ..
+    } = start;
+
+    let mut response = Response::new(ResponseBody::new(app_stream, trailers));
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
+}
+
+pin_project! {
+    /// Server stream, that sends request body as `RequestChunk` and `RequestTrailer` events.
+    ///
+    /// If the request body fails, `Disconnect` event is sent and the stream ends.
+    pub struct RequestStream<B> {
+        #[pin]
+        body: B,
+        data_end: bool,
+        end: bool,
+    }
+}
+
+impl<B> RequestStream<B> {
+    pub fn new(body: B) -> Self {
+        Self {
+            body,
+            data_end: false,
+            end: false,
+        }
+    }
+}
+
+impl<B: HttpBody> Stream for RequestStream<B> {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if *this.end {
+            return Poll::Ready(None);
+        }
+
+        if !*this.data_end {
+            let http_event = match ready!(this.body.as_mut().poll_data(cx)) {
+                Some(Ok(mut data)) => {
+                    let mut chunk = RequestChunk::default();
+                    chunk.body = data.copy_to_bytes(data.remaining());
+                    chunk.more = !this.body.is_end_stream();
+                    *this.end = !chunk.more;
+                    Some(HttpEvent::RequestChunk(chunk))
+                }
+                Some(Err(_)) => {
+                    *this.end = true;
+                    Some(HttpEvent::Disconnect(Disconnect::default()))
+                }
+                None => None,
+            };
+
+            match http_event {
+                Some(http_event) => {
+                    return Poll::Ready(Some(Event::typed::<HttpFamily>(http_event)));
+                }
+                // Trailers, if any, or the last empty chunk are sent after the data
+                None => *this.data_end = true,
+            }
+        }
+
+        let http_event = match ready!(this.body.poll_trailers(cx)) {
+            Ok(Some(headers)) => {
+                let mut trailer = RequestTrailer::default();
+                trailer.headers = headers;
+                HttpEvent::RequestTrailer(trailer)
+            }
+            Ok(None) => HttpEvent::RequestChunk(RequestChunk::default()),
+            Err(_) => HttpEvent::Disconnect(Disconnect::default()),
+        };
+        *this.end = true;
+
+        Poll::Ready(Some(Event::typed::<HttpFamily>(http_event)))
+    }
+}
+
+/// Response body, that is read from `ResponseChunk` and `ResponseTrailer` events of an app stream.
+pub struct ResponseBody<AS> {
+    stream: AS,
+    has_trailers: bool,
+    trailers: Option<HeaderMap>,
+    body_end: bool,
+    trailers_end: bool,
+}
+
+impl<AS> ResponseBody<AS> {
+    pub fn new(stream: AS, has_trailers: bool) -> Self {
+        Self {
+            stream,
+            has_trailers,
+            trailers: None,
+            body_end: false,
+            trailers_end: false,
+        }
+    }
+}
+
+impl<AS> ResponseBody<AS>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    /// Polls the next HTTP event, marking the body as ended if the app stream ends.
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<HttpEvent>> {
+        loop {
+            let Some(event) = ready!(self.stream.poll_next_unpin(cx)) else {
+                self.body_end = true;
+                self.trailers_end = true;
+                return Poll::Ready(None);
+            };
+
+            if let Some(event) = event.downcast::<HttpFamily>() {
+                return Poll::Ready(Some(event.clone()));
+            }
+        }
+    }
+
+    fn push_trailer(&mut self, trailer: &ResponseTrailer) {
+        self.body_end = true;
+        self.trailers_end = !trailer.more;
+        self.trailers
+            .get_or_insert_with(HeaderMap::new)
+            .extend(trailer.headers.clone());
+    }
+}
+
+impl<AS> HttpBody for ResponseBody<AS>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        while !self.body_end {
+            match ready!(self.poll_event(cx)) {
+                Some(HttpEvent::ResponseChunk(ResponseChunk { body, more, .. })) => {
+                    self.body_end = !more;
+                    if !body.is_empty() {
+                        return Poll::Ready(Some(Ok(body)));
+                    }
+                }
+                Some(HttpEvent::ResponseTrailer(trailer)) => self.push_trailer(&trailer),
+                _ => {}
+            }
+        }
+
+        Poll::Ready(None)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        while self.has_trailers && !self.trailers_end {
+            if let Some(HttpEvent::ResponseTrailer(trailer)) = ready!(self.poll_event(cx)) {
+                self.push_trailer(&trailer);
+            }
+        }
+
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body_end && (!self.has_trailers || self.trailers_end)
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{BoxError, ProtocolError};
+use bytes::{Buf, Bytes};
+use futures_core::future::BoxFuture;
+use futures_core::Stream;
+use futures_util::future::poll_fn;
+use futures_util::{FutureExt, StreamExt};
+use http::header::{CONTENT_TYPE, TRAILER};
+use http::{HeaderMap, Request, Response};
+use http_body::Body as HttpBody;
+use pin_project_lite::pin_project;
+use servio_http::http::{
+    HttpEvent, HttpFamily, HttpScope, RequestTrailer, ResponseChunk, ResponseStart,
+    ResponseTrailer, PROTOCOL_HTTP,
+};
+use servio_service::{Event, Scope, Service};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tower_service::Service as TowerService;
+
+/// Tower to Servio service wrapper. It can be used to transform `tower`-compatible HTTP service
+/// into Servio-compatible one.
+///
+/// Only `http` protocol is supported. Request body is read from `RequestChunk` events, and
+/// response body is sent as `ResponseChunk` events, followed by `ResponseTrailer`, if the response
+/// has `Trailer` header or gRPC content type. Original [`Scope`] is available to the tower service in request extensions.
+///
+/// The service is cloned for every request and the clone is driven to readiness before the call,
+/// so it must be cheap to clone.
+#[derive(Clone, Debug)]
+pub struct Tower2Servio<T> {
+    inner: T,
+}
+
+impl<T> Tower2Servio<T> {
+    pub fn new(service: T) -> Self {
+        Self { inner: service }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T, B, SS> Service<SS> for Tower2Servio<T>
+where
+    T: TowerService<Request<RequestBody<SS>>, Response = Response<B>> + Clone + Send + 'static,
+    T::Error: Into<BoxError>,
+    T::Future: Send,
+    B: HttpBody + Send + 'static,
+    SS: Stream<Item = Event> + Send + Unpin + 'static,
+{
+    type AppStream = ResponseStream<B>;
+    type Error = servio_service::BoxError;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let request = match make_request(scope, server_events) {
+            Ok(request) => request,
+            Err(e) => return futures_util::future::err(to_box_error(e)).boxed(),
+        };
+
+        let mut service = self.inner.clone();
+        async move {
+            poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(to_box_error)?;
+            let response = service.call(request).await.map_err(to_box_error)?;
+            Ok(ResponseStream::new(response))
+        }
+        .boxed()
+    }
+}
+
+fn to_box_error(e: impl Into<BoxError>) -> servio_service::BoxError {
+    servio_service::BoxError::from(e.into())
+}
+
+fn make_request<SS>(
+    scope: Scope,
+    server_events: SS,
+) -> Result<Request<RequestBody<SS>>, ProtocolError>
+where
+    SS: Stream<Item = Event> + Unpin,
+{
+    if scope.protocol() != PROTOCOL_HTTP {
+        return Err(ProtocolError::UnsupportedProtocol(
+            scope.protocol().to_owned().into(),
+        ));
+    }
+    let http_scope = scope
+        .get::<HttpScope>()
+        .ok_or(ProtocolError::MissingHttpScope)?;
+
+    let mut request = Request::new(RequestBody::new(server_events));
+    *request.method_mut() = http_scope.method.clone();
+    *request.uri_mut() = http_scope.uri().clone();
+    *request.version_mut() = http_scope.version;
+    *request.headers_mut() = http_scope.headers.clone();
+    request.extensions_mut().insert(scope);
+    Ok(request)
+}
+
+/// Request body, that is read from `RequestChunk` and `RequestTrailer` events of a server stream.
+///
+/// The body ends on the last chunk, the last trailer or on `Disconnect` event.
+pub struct RequestBody<SS> {
+    stream: SS,
+    trailers: Option<HeaderMap>,
+    data_end: bool,
+    trailers_end: bool,
+}
+
+impl<SS> RequestBody<SS> {
+    pub fn new(stream: SS) -> Self {
+        Self {
+            stream,
+            trailers: None,
+            data_end: false,
+            trailers_end: false,
+        }
+    }
+
+    fn end(&mut self) {
+        self.data_end = true;
+        self.trailers_end = true;
+    }
+
+    fn push_trailer(&mut self, trailer: &RequestTrailer) {
+        self.data_end = true;
+        self.trailers_end = !trailer.more;
+        self.trailers
+            .get_or_insert_with(HeaderMap::new)
+            .extend(trailer.headers.clone());
+    }
+}
+
+impl<SS> HttpBody for RequestBody<SS>
+where
+    SS: Stream<Item = Event> + Unpin,
+{
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        while !self.data_end {
+            let Some(event) = ready!(self.stream.poll_next_unpin(cx)) else {
+                self.end();
+                break;
+            };
+
+            match event.downcast::<HttpFamily>() {
+                Some(HttpEvent::RequestChunk(chunk)) => {
+                    if !chunk.more {
+                        self.end();
+                    }
+                    if !chunk.body.is_empty() {
+                        return Poll::Ready(Some(Ok(chunk.body.clone())));
+                    }
+                }
+                Some(HttpEvent::RequestTrailer(trailer)) => self.push_trailer(trailer),
+                Some(HttpEvent::Disconnect(_)) => self.end(),
+                _ => {}
+            }
+        }
+
+        Poll::Ready(None)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        while !self.trailers_end {
+            let Some(event) = ready!(self.stream.poll_next_unpin(cx)) else {
+                self.end();
+                break;
+            };
+
+            match event.downcast::<HttpFamily>() {
+                Some(HttpEvent::RequestTrailer(trailer)) => self.push_trailer(trailer),
+                Some(HttpEvent::Disconnect(_)) => self.end(),
+                _ => {}
+            }
+        }
+
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.data_end && self.trailers_end
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ResponseState {
+    Start,
+    Body,
+    Trailers,
+    End,
+}
+
+pin_project! {
+    /// App stream, that sends tower response as `ResponseStart`, `ResponseChunk` and
+    /// `ResponseTrailer` events.
+    ///
+    /// `ResponseStart` is sent as soon as the response is available. Trailers are announced, if
+    /// the response has `Trailer` header or gRPC content type, since gRPC trailers are sent without
+    /// `Trailer` header. If the body has no trailers after all, `ResponseTrailer` is sent with no
+    /// headers.
+    ///
+    /// If the response body fails, the stream ends without completing the response.
+    pub struct ResponseStream<B> {
+        start: Option<ResponseStart>,
+        #[pin]
+        body: B,
+        trailers: bool,
+        state: ResponseState,
+    }
+}
+
+impl<B> ResponseStream<B> {
+    pub fn new(response: Response<B>) -> Self {
+        let (parts, body) = response.into_parts();
+
+        let mut start = ResponseStart::default();
+        start.status = parts.status;
+        start.trailers = has_trailers(&parts.headers);
+        start.headers = parts.headers;
+
+        Self {
+            trailers: start.trailers,
+            start: Some(start),
+            body,
+            state: ResponseState::Start,
+        }
+    }
+}
+
+impl<B: HttpBody> Stream for ResponseStream<B> {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let after_body = if *this.trailers {
+            ResponseState::Trailers
+        } else {
+            ResponseState::End
+        };
+
+        let http_event = match *this.state {
+            ResponseState::Start => {
+                *this.state = ResponseState::Body;
+                HttpEvent::ResponseStart(this.start.take().unwrap())
+            }
+            ResponseState::Body => {
+                let mut chunk = ResponseChunk::default();
+                match ready!(this.body.as_mut().poll_data(cx)) {
+                    Some(Ok(mut data)) => {
+                        chunk.body = data.copy_to_bytes(data.remaining());
+                        chunk.more = !this.body.is_end_stream();
+                    }
+                    None => chunk.more = false,
+                    Some(Err(_)) => {
+                        *this.state = ResponseState::End;
+                        return Poll::Ready(None);
+                    }
+                }
+                if !chunk.more {
+                    *this.state = after_body;
+                }
+                HttpEvent::ResponseChunk(chunk)
+            }
+            ResponseState::Trailers => {
+                let trailers = ready!(this.body.poll_trailers(cx));
+                *this.state = ResponseState::End;
+                let Ok(headers) = trailers else {
+                    return Poll::Ready(None);
+                };
+                let mut trailer = ResponseTrailer::default();
+                trailer.headers = headers.unwrap_or_default();
+                HttpEvent::ResponseTrailer(trailer)
+            }
+            ResponseState::End => return Poll::Ready(None),
+        };
+
+        Poll::Ready(Some(Event::typed::<HttpFamily>(http_event)))
+    }
+}
+
+/// Returns `true`, if the response announces trailers with `Trailer` header, or is a gRPC one.
+fn has_trailers(headers: &HeaderMap) -> bool {
+    let grpc = headers
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .map_or(false, |h| h.starts_with("application/grpc"));
+    grpc || headers.contains_key(TRAILER)
+}
@@ -0,0 +1,213 @@
+use bytes::{Bytes, BytesMut};
+use futures_executor::block_on;
+use futures_util::stream::{self, StreamExt};
+use futures_util::task::noop_waker_ref;
+use http::header::{CONTENT_TYPE, TRAILER};
+use http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use http_body::Body as HttpBody;
+use servio_http::http::{HttpEvent, HttpFamily, HttpScope, PROTOCOL_HTTP};
+use servio_service::{Event, Scope};
+use servio_tower::{ReadyServio2Tower, RequestBody, Servio2Tower, Tower2Servio};
+use servio_util::limit::ConcurrencyLimit;
+use servio_util::response::PlainTextResponse;
+use servio_util::test::TestClient;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{service_fn, Service, ServiceBuilder, ServiceExt};
+
+/// Body with a single data frame, followed by trailers.
+struct TrailerBody {
+    data: Option<Bytes>,
+    trailers: Option<HeaderMap>,
+}
+
+impl HttpBody for TrailerBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Ready(self.data.take().map(Ok))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(self.trailers.take()))
+    }
+}
+
+/// Body, that never yields data.
+struct PendingBody;
+
+impl HttpBody for PendingBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Poll::Pending
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Pending
+    }
+}
+
+async fn collect<B>(mut body: B) -> BytesMut
+where
+    B: HttpBody<Data = Bytes> + Unpin,
+    B::Error: std::fmt::Debug,
+{
+    let mut collected = BytesMut::new();
+    while let Some(data) = body.data().await {
+        collected.extend_from_slice(&data.unwrap());
+    }
+    collected
+}
+
+#[test]
+fn tower_service() {
+    let echo = service_fn(|request: Request<_>| async move {
+        assert!(request.extensions().get::<Scope>().is_some());
+
+        let mut trailers = HeaderMap::new();
+        let path = HeaderValue::from_str(request.uri().path()).unwrap();
+        trailers.insert("x-path", path);
+
+        let body = TrailerBody {
+            data: Some(collect(request.into_body()).await.freeze()),
+            trailers: Some(trailers),
+        };
+        let response = Response::builder()
+            .status(StatusCode::CREATED)
+            .header(TRAILER, "x-path")
+            .body(body)
+            .unwrap();
+        Ok::<_, Infallible>(response)
+    });
+    let mut client = TestClient::new(Tower2Servio::new(echo));
+
+    let response = block_on(client.post("/echo").chunks(["Hello, ", "tower!"]).send()).unwrap();
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.text(), "Hello, tower!");
+    assert_eq!(response.trailers.unwrap()["x-path"], "/echo");
+}
+
+#[test]
+fn trailers_only_response() {
+    let grpc = service_fn(|_request: Request<_>| async move {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", HeaderValue::from_static("5"));
+        let body = TrailerBody {
+            data: None,
+            trailers: Some(trailers),
+        };
+        let response = Response::builder()
+            .header(CONTENT_TYPE, "application/grpc")
+            .body(body)
+            .unwrap();
+        Ok::<_, Infallible>(response)
+    });
+    let mut client = TestClient::new(Tower2Servio::new(grpc));
+
+    let response = block_on(client.post("/").send()).unwrap();
+    assert_eq!(response.text(), "");
+    assert_eq!(response.trailers.unwrap()["grpc-status"], "5");
+}
+
+#[test]
+fn response_start_before_body() {
+    let pending = service_fn(|_request: Request<_>| async move {
+        Ok::<_, Infallible>(Response::new(PendingBody))
+    });
+    let mut service = Tower2Servio::new(pending);
+
+    let scope = Scope::new(PROTOCOL_HTTP.into()).with_scope(HttpScope::default());
+    let app_stream = servio_service::Service::call(&mut service, scope, stream::empty::<Event>());
+    let mut app_stream = block_on(app_stream).unwrap();
+    let event = block_on(app_stream.next()).unwrap();
+    assert!(matches!(
+        event.downcast::<HttpFamily>(),
+        Some(HttpEvent::ResponseStart(..))
+    ));
+}
+
+#[test]
+fn ready_servio_service() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let first = ReadyServio2Tower::new(ConcurrencyLimit::new(app, 1));
+    let mut second = first.clone();
+    let mut poll_ready = move || {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        Service::<Request<String>>::poll_ready(&mut second, &mut cx).is_ready()
+    };
+
+    let response = block_on(first.oneshot(Request::new(String::new()))).unwrap();
+
+    // The slot is held by the response body
+    assert!(!poll_ready());
+    let body = block_on(collect(response.into_body()));
+    assert_eq!(body, BytesMut::from("Hello"));
+    assert!(poll_ready());
+}
+
+#[test]
+fn servio_service() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let service = Servio2Tower::new(app);
+
+    let request = Request::post("/").body(String::from("ignored")).unwrap();
+    let response = block_on(service.oneshot(request)).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/plain");
+
+    let body = block_on(collect(response.into_body()));
+    assert_eq!(body, BytesMut::from("Hello"));
+}
+
+#[test]
+fn tower_middleware() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let service = ServiceBuilder::new()
+        .map_response(|mut response: Response<_>| {
+            let headers = response.headers_mut();
+            headers.insert("x-middleware", HeaderValue::from_static("tower"));
+            response
+        })
+        .service(Servio2Tower::new(app));
+    let mut client = TestClient::new(Tower2Servio::new(service));
+
+    let response = block_on(client.get("/").send()).unwrap();
+    assert_eq!(response.headers["x-middleware"], "tower");
+    assert_eq!(response.text(), "Hello");
+}
+
+#[test]
+fn request_trailers() {
+    let echo = service_fn(|mut request: Request<RequestBody<_>>| async move {
+        let body = collect(request.body_mut()).await;
+        let trailers = request.body_mut().trailers().await.unwrap().unwrap();
+
+        let mut response = Response::new(String::from_utf8(body.to_vec()).unwrap());
+        response.headers_mut().extend(trailers);
+        Ok::<_, Infallible>(response)
+    });
+    // Trailers pass Servio to tower bridge and back
+    let service = Tower2Servio::new(Servio2Tower::new(Tower2Servio::new(echo)));
+    let mut client = TestClient::new(service);
+
+    let request = client.post("/").body("Hello").trailer("x-checksum", "abc");
+    let response = block_on(request.send()).unwrap();
+    assert_eq!(response.headers["x-checksum"], "abc");
+    assert_eq!(response.text(), "Hello");
+}
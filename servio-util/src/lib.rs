@@ -1 +1,6 @@
+pub mod limit;
+pub mod make;
+pub mod mount;
 pub mod response;
+pub mod test;
+pub mod validate;
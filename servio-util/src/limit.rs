@@ -0,0 +1,201 @@
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use servio_service::{Event, Layer, ReadyService, Scope, Service};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Waker};
+
+/// Limits the number of connections, that are processed by the inner service simultaneously.
+///
+/// A connection occupies a slot from the moment [`ReadyService::poll_ready`] succeeds until its
+/// `AppStream` is dropped. When all slots are occupied, `poll_ready` returns `Pending`, so the
+/// server stops dispatching new requests.
+pub struct ConcurrencyLimit<S> {
+    inner: S,
+    semaphore: Arc<Semaphore>,
+    permit: Option<Permit>,
+}
+
+impl<S> ConcurrencyLimit<S> {
+    /// Creates a limit of `max` simultaneous connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero, as such service would never become ready.
+    pub fn new(inner: S, max: usize) -> Self {
+        assert!(max > 0, "concurrency limit must be greater than zero");
+        Self {
+            inner,
+            semaphore: Arc::new(Semaphore::new(max)),
+            permit: None,
+        }
+    }
+}
+
+/// Clones share the limit, but not the acquired slot.
+impl<S: Clone> Clone for ConcurrencyLimit<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            semaphore: self.semaphore.clone(),
+            permit: None,
+        }
+    }
+}
+
+impl<S, SS> Service<SS> for ConcurrencyLimit<S>
+where
+    S: Service<SS>,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = LimitedStream<S::AppStream>;
+    type Error = S::Error;
+    type Future = LimitedFuture<S::Future>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let permit = self
+            .permit
+            .take()
+            .expect("poll_ready must be called before call");
+
+        LimitedFuture {
+            future: self.inner.call(scope, server_events),
+            permit: Some(permit),
+        }
+    }
+}
+
+impl<S, SS> ReadyService<SS> for ConcurrencyLimit<S>
+where
+    S: Service<SS>,
+    SS: Stream<Item = Event>,
+{
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.permit.is_none() {
+            self.permit = Some(ready!(self.semaphore.poll_acquire(cx)));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Layer, that wraps services with [`ConcurrencyLimit`]. Every wrapped service gets its own limit.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimitLayer {
+    max: usize,
+}
+
+impl ConcurrencyLimitLayer {
+    /// Creates a layer with limit of `max` simultaneous connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn new(max: usize) -> Self {
+        assert!(max > 0, "concurrency limit must be greater than zero");
+        Self { max }
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimit::new(inner, self.max)
+    }
+}
+
+pin_project! {
+    pub struct LimitedFuture<F> {
+        #[pin]
+        future: F,
+        permit: Option<Permit>,
+    }
+}
+
+impl<F, AS, E> Future for LimitedFuture<F>
+where
+    F: Future<Output = Result<AS, E>>,
+{
+    type Output = Result<LimitedStream<AS>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let app_stream = ready!(this.future.poll(cx))?;
+        Poll::Ready(Ok(LimitedStream {
+            stream: app_stream,
+            _permit: this.permit.take().expect("polled after completion"),
+        }))
+    }
+}
+
+pin_project! {
+    /// AppStream, that holds a slot of [`ConcurrencyLimit`] until dropped.
+    pub struct LimitedStream<S> {
+        #[pin]
+        stream: S,
+        _permit: Permit,
+    }
+}
+
+impl<S: Stream<Item = Event>> Stream for LimitedStream<S> {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().stream.poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+struct Semaphore {
+    state: Mutex<SemaphoreState>,
+}
+
+struct SemaphoreState {
+    available: usize,
+    waiters: Vec<Waker>,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new(SemaphoreState {
+                available: permits,
+                waiters: vec![],
+            }),
+        }
+    }
+
+    fn poll_acquire(self: &Arc<Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let mut state = self.state.lock().unwrap();
+        if state.available == 0 {
+            if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                state.waiters.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+
+        state.available -= 1;
+        Poll::Ready(Permit {
+            semaphore: self.clone(),
+        })
+    }
+}
+
+struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut state = self.semaphore.state.lock().unwrap();
+            state.available += 1;
+            std::mem::take(&mut state.waiters)
+        };
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
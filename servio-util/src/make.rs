@@ -0,0 +1,36 @@
+use futures_core::Stream;
+use servio_service::{Event, MakeService, Service};
+use std::future::Future;
+use std::marker::PhantomData;
+
+/// Service factory, created from a closure. See [`make_service_fn`].
+pub struct MakeServiceFn<F, Target> {
+    f: F,
+    _target: PhantomData<fn(Target)>,
+}
+
+/// Returns a new [`MakeServiceFn`], that calls `f` to create a service for each connection.
+pub fn make_service_fn<F, Target>(f: F) -> MakeServiceFn<F, Target> {
+    MakeServiceFn {
+        f,
+        _target: PhantomData,
+    }
+}
+
+impl<F, Fut, S, E, Target, ServerStream> MakeService<Target, ServerStream>
+    for MakeServiceFn<F, Target>
+where
+    F: FnMut(Target) -> Fut,
+    Fut: Future<Output = Result<S, E>>,
+    S: Service<ServerStream>,
+    E: std::error::Error,
+    ServerStream: Stream<Item = Event>,
+{
+    type Service = S;
+    type MakeError = E;
+    type Future = Fut;
+
+    fn make_service(&mut self, target: Target) -> Self::Future {
+        (self.f)(target)
+    }
+}
@@ -0,0 +1,81 @@
+//! Middleware, that routes requests to an app, mounted at a path prefix.
+
+use futures_core::Stream;
+use servio_http::http::HttpScope;
+use servio_service::{EitherFuture, EitherStream, Event, Layer, Scope, Service};
+use std::borrow::Cow;
+
+/// Routes requests under `prefix` to the mounted app, and other requests to the fallback.
+///
+/// The mounted app gets `HttpScope` with the prefix appended to `root_path` by
+/// [`HttpScope::mount`], so that `path` keeps including `root_path`. Prefixes are matched by
+/// whole segments, and nested mounts are relative to the outer ones. Scopes without `HttpScope`,
+/// like lifespan ones, go to the fallback.
+#[derive(Clone, Debug)]
+pub struct Mount<S, F> {
+    prefix: Cow<'static, str>,
+    inner: S,
+    fallback: F,
+}
+
+impl<S, F> Mount<S, F> {
+    pub fn new(prefix: impl Into<Cow<'static, str>>, inner: S, fallback: F) -> Self {
+        Self {
+            prefix: prefix.into(),
+            inner,
+            fallback,
+        }
+    }
+}
+
+impl<S, F, SS> Service<SS> for Mount<S, F>
+where
+    S: Service<SS>,
+    F: Service<SS, Error = S::Error>,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = EitherStream<S::AppStream, F::AppStream>;
+    type Error = S::Error;
+    type Future = EitherFuture<S::Future, F::Future>;
+
+    fn call(&mut self, mut scope: Scope, server_events: SS) -> Self::Future {
+        if let Some(http_scope) = scope.get_ref::<HttpScope>() {
+            let mut http_scope = http_scope.clone();
+            if http_scope.mount(&self.prefix) {
+                scope.insert(http_scope);
+                return EitherFuture::A {
+                    inner: self.inner.call(scope, server_events),
+                };
+            }
+        }
+
+        EitherFuture::B {
+            inner: self.fallback.call(scope, server_events),
+        }
+    }
+}
+
+/// Layer, that mounts the app at the prefix in front of the wrapped service, which becomes
+/// the fallback of [`Mount`].
+#[derive(Clone, Debug)]
+pub struct MountLayer<S> {
+    prefix: Cow<'static, str>,
+    inner: S,
+}
+
+impl<S> MountLayer<S> {
+    pub fn new(prefix: impl Into<Cow<'static, str>>, inner: S) -> Self {
+        Self {
+            prefix: prefix.into(),
+            inner,
+        }
+    }
+}
+
+impl<S: Clone, F> Layer<F> for MountLayer<S> {
+    type Service = Mount<S, F>;
+
+    fn layer(&self, fallback: F) -> Self::Service {
+        Mount::new(self.prefix.clone(), self.inner.clone(), fallback)
+    }
+}
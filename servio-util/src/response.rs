@@ -3,7 +3,7 @@ use futures_core::Stream;
 use futures_util::{future::Ready, stream::Iter};
 use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use http::{HeaderMap, StatusCode};
-use servio_http::http::{HttpEvent, ResponseChunk, ResponseStart, EVENT_HTTP};
+use servio_http::http::{HttpEvent, HttpFamily, ResponseChunk, ResponseStart};
 use servio_service::{Event, Scope, Service};
 use std::borrow::Cow;
 use std::convert::Infallible;
@@ -34,8 +34,8 @@ impl StaticResponse {
 
         Self {
             events: vec![
-                Event::new(EVENT_HTTP.into(), HttpEvent::ResponseStart(response_start)),
-                Event::new(EVENT_HTTP.into(), HttpEvent::ResponseChunk(resp)),
+                Event::typed::<HttpFamily>(HttpEvent::ResponseStart(response_start)),
+                Event::typed::<HttpFamily>(HttpEvent::ResponseChunk(resp)),
             ],
         }
     }
@@ -0,0 +1,479 @@
+//! In-process client for testing Servio services without a socket.
+//!
+//! [`TestClient`] builds `Scope`, feeds scripted events to the service as a server stream and
+//! collects the app stream into a structured response.
+
+use bytes::{Bytes, BytesMut};
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_core::Stream;
+use futures_util::stream::Peekable;
+use futures_util::StreamExt;
+use http::header::{HeaderName, SEC_WEBSOCKET_PROTOCOL};
+use http::{HeaderMap, HeaderValue, Method, StatusCode, Version};
+use servio_http::http::{
+    Disconnect as HttpDisconnect, HttpEvent, HttpFamily, HttpScope, RequestChunk, RequestTrailer,
+    ResponseChunk, ResponseStart, ResponseTrailer, PROTOCOL_HTTP,
+};
+use servio_http::websocket::{
+    Accept, BinaryFrame, Close, Connect, Disconnect, Ping, TextFrame, WebSocketEvent,
+    WebSocketFamily, WebSocketScope, PROTOCOL_WEBSOCKET,
+};
+use servio_service::{Event, EventFamily, Scope, Service};
+use std::any::Any;
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Server stream, that is passed to services by [`TestClient`].
+pub struct TestServerStream {
+    rx: UnboundedReceiver<Event>,
+}
+
+impl Stream for TestServerStream {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx)
+    }
+}
+
+/// In-process client, that drives a service directly.
+pub struct TestClient<S> {
+    service: S,
+}
+
+impl<S> TestClient<S>
+where
+    S: Service<TestServerStream>,
+{
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    /// Returns a reference to the wrapped service.
+    pub fn service(&mut self) -> &mut S {
+        &mut self.service
+    }
+
+    /// Starts building an HTTP request.
+    pub fn request(&mut self, method: Method, uri: &str) -> TestRequest<'_, S> {
+        let mut http_scope = HttpScope::default();
+        http_scope.method = method;
+        http_scope.set_uri(uri.parse().expect("invalid uri"));
+        http_scope.version = Version::HTTP_11;
+
+        TestRequest {
+            service: &mut self.service,
+            scope: Scope::new(PROTOCOL_HTTP.into()),
+            http_scope,
+            chunks: vec![],
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::GET, uri)
+    }
+
+    pub fn post(&mut self, uri: &str) -> TestRequest<'_, S> {
+        self.request(Method::POST, uri)
+    }
+
+    /// Starts building a WebSocket connection.
+    pub fn websocket(&mut self, uri: &str) -> TestWebSocket<'_, S> {
+        let mut http_scope = HttpScope::default();
+        http_scope.method = Method::GET;
+        http_scope.scheme = "ws".into();
+        http_scope.set_uri(uri.parse().expect("invalid uri"));
+        http_scope.version = Version::HTTP_11;
+
+        TestWebSocket {
+            service: &mut self.service,
+            scope: Scope::new(PROTOCOL_WEBSOCKET.into()),
+            http_scope,
+            ws_scope: WebSocketScope::default(),
+        }
+    }
+}
+
+/// Builder of an HTTP request. See [`TestClient::request`].
+pub struct TestRequest<'a, S> {
+    service: &'a mut S,
+    scope: Scope,
+    http_scope: HttpScope,
+    chunks: Vec<Bytes>,
+    trailers: HeaderMap,
+}
+
+impl<'a, S> TestRequest<'a, S>
+where
+    S: Service<TestServerStream>,
+{
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        let (Ok(key), Ok(value)) = (key.try_into(), value.try_into()) else {
+            panic!("invalid header");
+        };
+        self.http_scope.headers.append(key, value);
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.http_scope.version = version;
+        self
+    }
+
+    /// Sets request body, sent as a single chunk.
+    pub fn body(self, body: impl Into<Bytes>) -> Self {
+        self.chunks(std::iter::once(body.into()))
+    }
+
+    /// Sets request body, sent as multiple chunks.
+    pub fn chunks<I>(mut self, chunks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Bytes>,
+    {
+        self.chunks = chunks.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Appends request trailer, sent after the body.
+    pub fn trailer<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        let (Ok(key), Ok(value)) = (key.try_into(), value.try_into()) else {
+            panic!("invalid trailer");
+        };
+        self.trailers.append(key, value);
+        self
+    }
+
+    /// Inserts additional scope, like it is done by servers or middlewares.
+    pub fn scope<T: Any + Send + Sync>(mut self, scope: T) -> Self {
+        self.scope.insert(scope);
+        self
+    }
+
+    /// Sends the request and collects the response. Like servers, the client keeps server stream
+    /// open after the request body, and sends `Disconnect`, when the response is complete or this
+    /// future is dropped.
+    ///
+    /// Panics, if the service violates HTTP protocol.
+    pub async fn send(self) -> Result<TestResponse, S::Error> {
+        let (tx, rx) = unbounded();
+
+        let has_trailers = !self.trailers.is_empty();
+        let count = self.chunks.len();
+        for (i, body) in self.chunks.into_iter().enumerate() {
+            let mut chunk = RequestChunk::default();
+            chunk.body = body;
+            chunk.more = i + 1 < count || has_trailers;
+            send::<HttpFamily>(&tx, HttpEvent::RequestChunk(chunk));
+        }
+        if count == 0 && !has_trailers {
+            send::<HttpFamily>(&tx, HttpEvent::RequestChunk(Default::default()));
+        }
+        if has_trailers {
+            let mut trailer = RequestTrailer::default();
+            trailer.headers = self.trailers;
+            send::<HttpFamily>(&tx, HttpEvent::RequestTrailer(trailer));
+        }
+        let _disconnect = DisconnectGuard(tx);
+
+        let scope = self.scope.with_scope(self.http_scope);
+        let app_stream = self.service.call(scope, TestServerStream { rx }).await?;
+        Ok(TestResponse::collect(app_stream).await)
+    }
+}
+
+/// Sends `Disconnect` event and closes server stream, when dropped.
+struct DisconnectGuard(UnboundedSender<Event>);
+
+impl Drop for DisconnectGuard {
+    fn drop(&mut self) {
+        send::<HttpFamily>(&self.0, HttpEvent::Disconnect(HttpDisconnect::default()));
+    }
+}
+
+/// Response, collected from app stream.
+#[derive(Clone, Debug)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub trailers: Option<HeaderMap>,
+}
+
+impl TestResponse {
+    /// Returns body as UTF-8 text.
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("body is not UTF-8")
+    }
+
+    async fn collect<AS>(app_stream: AS) -> Self
+    where
+        AS: Stream<Item = Event>,
+    {
+        let mut app_stream = Box::pin(app_stream);
+
+        let start = match next_http_event(&mut app_stream).await {
+            Some(HttpEvent::ResponseStart(start)) => start,
+            event => panic!("expected ResponseStart, got {event:?}"),
+        };
+        let ResponseStart {
+            status,
+            headers,
+            trailers,
+            ..
+        } = start;
+
+        let mut body = BytesMut::new();
+        loop {
+            match next_http_event(&mut app_stream).await {
+                Some(HttpEvent::ResponseChunk(ResponseChunk {
+                    body: chunk, more, ..
+                })) => {
+                    body.extend_from_slice(&chunk);
+                    if !more {
+                        break;
+                    }
+                }
+                None => break,
+                event => panic!("expected ResponseChunk, got {event:?}"),
+            }
+        }
+
+        let trailers = if trailers {
+            let mut trailers = HeaderMap::new();
+            loop {
+                match next_http_event(&mut app_stream).await {
+                    Some(HttpEvent::ResponseTrailer(ResponseTrailer { headers, more, .. })) => {
+                        trailers.extend(headers);
+                        if !more {
+                            break;
+                        }
+                    }
+                    None => break,
+                    event => panic!("expected ResponseTrailer, got {event:?}"),
+                }
+            }
+            Some(trailers)
+        } else {
+            None
+        };
+
+        Self {
+            status,
+            headers,
+            body: body.freeze(),
+            trailers,
+        }
+    }
+}
+
+async fn next_http_event<AS>(app_stream: &mut AS) -> Option<HttpEvent>
+where
+    AS: Stream<Item = Event> + Unpin,
+{
+    let event = app_stream.next().await?;
+    let event = event.downcast::<HttpFamily>();
+    Some(event.expect("unexpected event family").clone())
+}
+
+/// Builder of a WebSocket connection. See [`TestClient::websocket`].
+pub struct TestWebSocket<'a, S> {
+    service: &'a mut S,
+    scope: Scope,
+    http_scope: HttpScope,
+    ws_scope: WebSocketScope,
+}
+
+impl<'a, S> TestWebSocket<'a, S>
+where
+    S: Service<TestServerStream>,
+{
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        let (Ok(key), Ok(value)) = (key.try_into(), value.try_into()) else {
+            panic!("invalid header");
+        };
+        self.http_scope.headers.append(key, value);
+        self
+    }
+
+    /// Adds a subprotocol, requested by client.
+    pub fn subprotocol(mut self, subprotocol: impl Into<Cow<'static, str>>) -> Self {
+        let subprotocol = subprotocol.into();
+        self.http_scope.headers.append(
+            SEC_WEBSOCKET_PROTOCOL,
+            subprotocol.parse().expect("invalid subprotocol"),
+        );
+        self.ws_scope.subprotocols.push(subprotocol);
+        self
+    }
+
+    /// Inserts additional scope, like it is done by servers or middlewares.
+    pub fn scope<T: Any + Send + Sync>(mut self, scope: T) -> Self {
+        self.scope.insert(scope);
+        self
+    }
+
+    /// Sends `Connect` event and waits for the application to accept or reject the connection.
+    ///
+    /// Panics, if the service violates WebSocket protocol.
+    pub async fn connect(self) -> Result<TestWebSocketResult<S::AppStream>, S::Error> {
+        let (tx, rx) = unbounded();
+        send::<WebSocketFamily>(&tx, WebSocketEvent::Connect(Connect::default()));
+
+        let scope = self
+            .scope
+            .with_scope(self.http_scope)
+            .with_scope(self.ws_scope);
+        let app_stream = self.service.call(scope, TestServerStream { rx }).await?;
+        let mut app_stream = Box::pin(app_stream.peekable());
+
+        let Some(event) = app_stream.as_mut().peek().await else {
+            return Ok(TestWebSocketResult::Closed(Close::default()));
+        };
+
+        if event.downcast::<HttpFamily>().is_some() {
+            let response = TestResponse::collect(app_stream).await;
+            return Ok(TestWebSocketResult::Denied(response));
+        }
+
+        let event = app_stream.next().await.unwrap();
+        match event.downcast::<WebSocketFamily>() {
+            Some(WebSocketEvent::Accept(accept)) => {
+                Ok(TestWebSocketResult::Accepted(TestWebSocketSession {
+                    accept: accept.clone(),
+                    tx,
+                    app_stream,
+                }))
+            }
+            Some(WebSocketEvent::Close(close)) => Ok(TestWebSocketResult::Closed(close.clone())),
+            event => panic!("expected Accept or Close, got {event:?}"),
+        }
+    }
+}
+
+/// Outcome of a WebSocket handshake.
+pub enum TestWebSocketResult<AS: Stream> {
+    /// Application has accepted the connection.
+    Accepted(TestWebSocketSession<AS>),
+    /// Application has closed the connection before accepting it.
+    Closed(Close),
+    /// Application has responded with HTTP response instead of accepting the connection.
+    Denied(TestResponse),
+}
+
+impl<AS: Stream> TestWebSocketResult<AS> {
+    /// Returns an accepted session. Panics, if the connection was not accepted.
+    pub fn unwrap(self) -> TestWebSocketSession<AS> {
+        match self {
+            TestWebSocketResult::Accepted(session) => session,
+            TestWebSocketResult::Closed(close) => panic!("connection closed: {close:?}"),
+            TestWebSocketResult::Denied(response) => panic!("connection denied: {response:?}"),
+        }
+    }
+}
+
+/// Accepted WebSocket connection.
+pub struct TestWebSocketSession<AS: Stream> {
+    accept: Accept,
+    tx: UnboundedSender<Event>,
+    app_stream: Pin<Box<Peekable<AS>>>,
+}
+
+impl<AS> TestWebSocketSession<AS>
+where
+    AS: Stream<Item = Event>,
+{
+    /// Returns `Accept` event, sent by the application.
+    pub fn accept(&self) -> &Accept {
+        &self.accept
+    }
+
+    pub fn send_text(&self, data: impl Into<String>) {
+        let mut frame = TextFrame::default();
+        frame.data = data.into();
+        send::<WebSocketFamily>(&self.tx, WebSocketEvent::TextFrame(frame));
+    }
+
+    pub fn send_binary(&self, data: impl Into<Bytes>) {
+        let mut frame = BinaryFrame::default();
+        frame.data = data.into();
+        send::<WebSocketFamily>(&self.tx, WebSocketEvent::BinaryFrame(frame));
+    }
+
+    pub fn send_ping(&self, data: impl Into<Bytes>) {
+        let mut ping = Ping::default();
+        ping.data = data.into();
+        send::<WebSocketFamily>(&self.tx, WebSocketEvent::Ping(ping));
+    }
+
+    /// Sends an arbitrary event to the application.
+    pub fn send_event(&self, event: Event) {
+        let _ = self.tx.unbounded_send(event);
+    }
+
+    /// Simulates client disconnect with the specified close code.
+    pub fn disconnect(self, code: u16) {
+        let mut disconnect = Disconnect::default();
+        disconnect.code = code;
+        send::<WebSocketFamily>(&self.tx, WebSocketEvent::Disconnect(disconnect));
+    }
+
+    /// Receives next WebSocket event from the application.
+    ///
+    /// Returns `None`, if app stream has ended.
+    pub async fn receive(&mut self) -> Option<WebSocketEvent> {
+        let event = self.app_stream.next().await?;
+        let event = event.downcast::<WebSocketFamily>();
+        Some(event.expect("unexpected event family").clone())
+    }
+
+    /// Receives next text message, joining its fragments. Panics on any other event.
+    pub async fn receive_text(&mut self) -> String {
+        let mut data = String::new();
+        loop {
+            match self.receive().await {
+                Some(WebSocketEvent::TextFrame(frame)) => {
+                    data.push_str(&frame.data);
+                    if !frame.more {
+                        return data;
+                    }
+                }
+                event => panic!("expected TextFrame, got {event:?}"),
+            }
+        }
+    }
+
+    /// Receives next binary message, joining its fragments. Panics on any other event.
+    pub async fn receive_binary(&mut self) -> Bytes {
+        let mut data = BytesMut::new();
+        loop {
+            match self.receive().await {
+                Some(WebSocketEvent::BinaryFrame(frame)) => {
+                    data.extend_from_slice(&frame.data);
+                    if !frame.more {
+                        return data.freeze();
+                    }
+                }
+                event => panic!("expected BinaryFrame, got {event:?}"),
+            }
+        }
+    }
+}
+
+fn send<F: EventFamily>(tx: &UnboundedSender<Event>, event: F::Payload) {
+    let _ = tx.unbounded_send(Event::typed::<F>(event));
+}
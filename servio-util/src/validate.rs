@@ -0,0 +1,398 @@
+//! Middleware, that checks app events against HTTP and WebSocket protocol state machines.
+//!
+//! [`Validate`] wraps any service and watches events, that it sends to server. When the service
+//! violates the protocol, for example sends `ResponseChunk` before `ResponseStart`, a typed
+//! [`ProtocolViolation`] is passed to a handler and the app stream is terminated, so server never
+//! receives an invalid sequence of events.
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+use servio_http::http::{
+    HttpEvent, HttpFamily, ResponseChunk, ResponseTrailer, EVENT_HTTP, PROTOCOL_HTTP,
+};
+use servio_http::websocket::{
+    BinaryFrame, TextFrame, WebSocketEvent, WebSocketFamily, EVENT_WEBSOCKET, PROTOCOL_WEBSOCKET,
+};
+use servio_service::{Event, EventFamily, Layer, Scope, Service};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Violation of a protocol, detected by [`Validate`] middleware.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtocolViolation {
+    /// Event is not allowed in the current state of the protocol.
+    UnexpectedEvent {
+        expected: &'static str,
+        got: &'static str,
+    },
+    /// Event was sent after the response or connection was completed.
+    EventAfterEnd { got: &'static str },
+    /// App stream ended, before the response was completed.
+    UnexpectedEnd { expected: &'static str },
+    /// Event payload does not match its family.
+    InvalidPayload { family: String },
+}
+
+impl fmt::Display for ProtocolViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolViolation::UnexpectedEvent { expected, got } => {
+                write!(f, "expected {expected}, got {got}")
+            }
+            ProtocolViolation::EventAfterEnd { got } => write!(f, "{got} sent after the end"),
+            ProtocolViolation::UnexpectedEnd { expected } => {
+                write!(f, "app stream ended, expected {expected}")
+            }
+            ProtocolViolation::InvalidPayload { family } => {
+                write!(f, "invalid payload for event family {family}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolViolation {}
+
+/// Default violation handler, that logs violations as errors.
+pub fn log_violation(violation: ProtocolViolation) {
+    tracing::error!(%violation, "Protocol violation");
+}
+
+/// Violation handler, that panics. Useful in tests.
+pub fn panic_on_violation(violation: ProtocolViolation) {
+    panic!("Protocol violation: {violation}");
+}
+
+/// Middleware, that validates events, sent by the wrapped service. See [module docs](self).
+#[derive(Clone)]
+pub struct Validate<S, F = fn(ProtocolViolation)> {
+    inner: S,
+    on_violation: F,
+}
+
+impl<S> Validate<S> {
+    /// Creates `Validate` middleware, that logs violations.
+    pub fn new(inner: S) -> Self {
+        Self::with_handler(inner, log_violation)
+    }
+}
+
+impl<S, F> Validate<S, F> {
+    /// Creates `Validate` middleware with a custom violation handler.
+    pub fn with_handler(inner: S, on_violation: F) -> Self {
+        Self {
+            inner,
+            on_violation,
+        }
+    }
+}
+
+impl<S, F, SS> Service<SS> for Validate<S, F>
+where
+    S: Service<SS>,
+    F: FnMut(ProtocolViolation) + Clone,
+    SS: Stream<Item = Event>,
+{
+    type AppStream = ValidateStream<S::AppStream, F>;
+    type Error = S::Error;
+    type Future = ValidateFuture<S::Future, F>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let machine = match scope.protocol() {
+            PROTOCOL_HTTP => Machine::Http(HttpState::Start),
+            PROTOCOL_WEBSOCKET => Machine::WebSocket(WebSocketState::Handshake),
+            _ => Machine::Unknown,
+        };
+
+        ValidateFuture {
+            future: self.inner.call(scope, server_events),
+            state: Some((machine, self.on_violation.clone())),
+        }
+    }
+}
+
+/// Layer, that wraps services with [`Validate`].
+#[derive(Clone)]
+pub struct ValidateLayer<F = fn(ProtocolViolation)> {
+    on_violation: F,
+}
+
+impl ValidateLayer {
+    /// Creates a layer, that logs violations.
+    pub fn new() -> Self {
+        Self::with_handler(log_violation)
+    }
+}
+
+impl Default for ValidateLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> ValidateLayer<F> {
+    /// Creates a layer with a custom violation handler.
+    pub fn with_handler(on_violation: F) -> Self {
+        Self { on_violation }
+    }
+}
+
+impl<S, F: Clone> Layer<S> for ValidateLayer<F> {
+    type Service = Validate<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Validate::with_handler(inner, self.on_violation.clone())
+    }
+}
+
+pin_project! {
+    pub struct ValidateFuture<Fut, F> {
+        #[pin]
+        future: Fut,
+        state: Option<(Machine, F)>,
+    }
+}
+
+impl<Fut, F, AS, E> Future for ValidateFuture<Fut, F>
+where
+    Fut: Future<Output = Result<AS, E>>,
+{
+    type Output = Result<ValidateStream<AS, F>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let stream = ready!(this.future.poll(cx))?;
+        let (machine, on_violation) = this.state.take().expect("polled after completion");
+        Poll::Ready(Ok(ValidateStream {
+            stream,
+            machine,
+            on_violation,
+            terminated: false,
+        }))
+    }
+}
+
+pin_project! {
+    /// App stream, that validates events of the wrapped stream.
+    pub struct ValidateStream<St, F> {
+        #[pin]
+        stream: St,
+        machine: Machine,
+        on_violation: F,
+        terminated: bool,
+    }
+}
+
+impl<St, F> Stream for ValidateStream<St, F>
+where
+    St: Stream<Item = Event>,
+    F: FnMut(ProtocolViolation),
+{
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if *this.terminated {
+            return Poll::Ready(None);
+        }
+
+        let violation = match ready!(this.stream.poll_next(cx)) {
+            Some(event) => match this.machine.next(&event) {
+                Ok(()) => return Poll::Ready(Some(event)),
+                Err(violation) => violation,
+            },
+            None => match this.machine.end() {
+                Ok(()) => {
+                    *this.terminated = true;
+                    return Poll::Ready(None);
+                }
+                Err(violation) => violation,
+            },
+        };
+
+        *this.terminated = true;
+        (this.on_violation)(violation);
+        Poll::Ready(None)
+    }
+}
+
+enum Machine {
+    Http(HttpState),
+    WebSocket(WebSocketState),
+    Unknown,
+}
+
+#[derive(Clone, Copy)]
+enum HttpState {
+    Start,
+    Body { trailers: bool },
+    Trailers,
+    End,
+}
+
+#[derive(Clone, Copy)]
+enum WebSocketState {
+    Handshake,
+    /// Connection is accepted. Holds the name of a frame event, if its message is not complete.
+    Connected {
+        fragment: Option<&'static str>,
+    },
+    /// Connection was denied with HTTP response.
+    Denied(HttpState),
+    Closed,
+}
+
+impl Machine {
+    fn next(&mut self, event: &Event) -> Result<(), ProtocolViolation> {
+        match self {
+            Machine::Http(state) => {
+                if event.family() == EVENT_HTTP {
+                    *state = state.next(payload::<HttpFamily>(event)?)?;
+                }
+            }
+            Machine::WebSocket(state) => {
+                *state = match (*state, event.family()) {
+                    (WebSocketState::Handshake, EVENT_HTTP) => WebSocketState::Denied(
+                        HttpState::Start.next(payload::<HttpFamily>(event)?)?,
+                    ),
+                    (WebSocketState::Denied(http_state), EVENT_HTTP) => {
+                        WebSocketState::Denied(http_state.next(payload::<HttpFamily>(event)?)?)
+                    }
+                    (state, EVENT_WEBSOCKET) => state.next(payload::<WebSocketFamily>(event)?)?,
+                    (state, _) => state,
+                };
+            }
+            Machine::Unknown => {}
+        }
+        Ok(())
+    }
+
+    fn end(&self) -> Result<(), ProtocolViolation> {
+        let expected = match self {
+            Machine::Http(HttpState::Start)
+            | Machine::WebSocket(WebSocketState::Denied(HttpState::Start)) => "ResponseStart",
+            Machine::Http(HttpState::Body { .. })
+            | Machine::WebSocket(WebSocketState::Denied(HttpState::Body { .. })) => "ResponseChunk",
+            Machine::Http(HttpState::Trailers)
+            | Machine::WebSocket(WebSocketState::Denied(HttpState::Trailers)) => "ResponseTrailer",
+            Machine::WebSocket(WebSocketState::Handshake) => "Accept or Close",
+            _ => return Ok(()),
+        };
+        Err(ProtocolViolation::UnexpectedEnd { expected })
+    }
+}
+
+impl HttpState {
+    fn next(self, event: &HttpEvent) -> Result<Self, ProtocolViolation> {
+        let got = http_event_name(event);
+        match (self, event) {
+            (HttpState::Start, HttpEvent::ResponseStart(start)) => Ok(HttpState::Body {
+                trailers: start.trailers,
+            }),
+            (
+                HttpState::Body { trailers },
+                HttpEvent::ResponseChunk(ResponseChunk { more, .. }),
+            ) => Ok(match (*more, trailers) {
+                (true, _) => self,
+                (false, true) => HttpState::Trailers,
+                (false, false) => HttpState::End,
+            }),
+            (HttpState::Trailers, HttpEvent::ResponseTrailer(ResponseTrailer { more, .. })) => {
+                Ok(if *more { self } else { HttpState::End })
+            }
+            (HttpState::End, _) => Err(ProtocolViolation::EventAfterEnd { got }),
+            (HttpState::Start, _) => Err(ProtocolViolation::UnexpectedEvent {
+                expected: "ResponseStart",
+                got,
+            }),
+            (HttpState::Body { .. }, _) => Err(ProtocolViolation::UnexpectedEvent {
+                expected: "ResponseChunk",
+                got,
+            }),
+            (HttpState::Trailers, _) => Err(ProtocolViolation::UnexpectedEvent {
+                expected: "ResponseTrailer",
+                got,
+            }),
+        }
+    }
+}
+
+impl WebSocketState {
+    fn next(self, event: &WebSocketEvent) -> Result<Self, ProtocolViolation> {
+        let got = websocket_event_name(event);
+        match (self, event) {
+            (WebSocketState::Handshake, WebSocketEvent::Accept(..)) => {
+                Ok(WebSocketState::Connected { fragment: None })
+            }
+            (
+                WebSocketState::Handshake | WebSocketState::Connected { .. },
+                WebSocketEvent::Close(..),
+            ) => Ok(WebSocketState::Closed),
+            (
+                WebSocketState::Connected { fragment },
+                WebSocketEvent::TextFrame(TextFrame { more, .. })
+                | WebSocketEvent::BinaryFrame(BinaryFrame { more, .. }),
+            ) => match fragment {
+                // Frames of a fragmented message can't be interleaved with other messages
+                Some(expected) if expected != got => {
+                    Err(ProtocolViolation::UnexpectedEvent { expected, got })
+                }
+                _ => Ok(WebSocketState::Connected {
+                    fragment: more.then_some(got),
+                }),
+            },
+            (
+                WebSocketState::Connected { .. },
+                WebSocketEvent::Ping(..) | WebSocketEvent::Pong(..),
+            ) => Ok(self),
+            (WebSocketState::Closed | WebSocketState::Denied(..), _) => {
+                Err(ProtocolViolation::EventAfterEnd { got })
+            }
+            (WebSocketState::Handshake, _) => Err(ProtocolViolation::UnexpectedEvent {
+                expected: "Accept or Close",
+                got,
+            }),
+            (WebSocketState::Connected { .. }, _) => Err(ProtocolViolation::UnexpectedEvent {
+                expected: "TextFrame, BinaryFrame, Ping, Pong or Close",
+                got,
+            }),
+        }
+    }
+}
+
+/// Returns event payload, reporting events with family name, that disagrees with payload type.
+fn payload<F: EventFamily>(event: &Event) -> Result<&F::Payload, ProtocolViolation> {
+    event
+        .downcast::<F>()
+        .ok_or_else(|| ProtocolViolation::InvalidPayload {
+            family: event.family().to_string(),
+        })
+}
+
+fn http_event_name(event: &HttpEvent) -> &'static str {
+    match event {
+        HttpEvent::RequestChunk(..) => "RequestChunk",
+        HttpEvent::RequestTrailer(..) => "RequestTrailer",
+        HttpEvent::ResponseChunk(..) => "ResponseChunk",
+        HttpEvent::ResponseStart(..) => "ResponseStart",
+        HttpEvent::ResponseTrailer(..) => "ResponseTrailer",
+        HttpEvent::Disconnect(..) => "Disconnect",
+        _ => "unknown event",
+    }
+}
+
+fn websocket_event_name(event: &WebSocketEvent) -> &'static str {
+    match event {
+        WebSocketEvent::Connect(..) => "Connect",
+        WebSocketEvent::Accept(..) => "Accept",
+        WebSocketEvent::TextFrame(..) => "TextFrame",
+        WebSocketEvent::BinaryFrame(..) => "BinaryFrame",
+        WebSocketEvent::Disconnect(..) => "Disconnect",
+        WebSocketEvent::Close(..) => "Close",
+        WebSocketEvent::Ping(..) => "Ping",
+        WebSocketEvent::Pong(..) => "Pong",
+        _ => "unknown event",
+    }
+}
@@ -0,0 +1,57 @@
+use futures_executor::block_on;
+use futures_util::stream;
+use futures_util::task::noop_waker_ref;
+use http::{HeaderMap, StatusCode};
+use servio_service::{Event, Layer, ReadyService, Scope, Service};
+use servio_util::limit::{ConcurrencyLimit, ConcurrencyLimitLayer};
+use servio_util::response::PlainTextResponse;
+use std::task::{Context, Poll};
+
+type ServerStream = stream::Empty<Event>;
+
+fn poll_ready<S: ReadyService<ServerStream>>(service: &mut S) -> bool {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    matches!(service.poll_ready(&mut cx), Poll::Ready(Ok(())))
+}
+
+#[test]
+fn saturated_limit() {
+    let app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+    let mut first = ConcurrencyLimitLayer::new(2).layer(app);
+    let mut second = first.clone();
+    let mut third = first.clone();
+
+    assert!(poll_ready(&mut first));
+    let first_stream = block_on(first.call(Scope::new("test".into()), stream::empty())).unwrap();
+
+    assert!(poll_ready(&mut second));
+    let second_stream = block_on(second.call(Scope::new("test".into()), stream::empty())).unwrap();
+
+    // Both slots are occupied by active app streams
+    assert!(!poll_ready(&mut third));
+
+    drop(first_stream);
+    assert!(poll_ready(&mut third));
+
+    // Readiness is kept until the next call
+    assert!(poll_ready(&mut third));
+    assert!(!poll_ready(&mut first));
+
+    drop(second_stream);
+    assert!(poll_ready(&mut first));
+}
+
+#[test]
+#[should_panic(expected = "poll_ready must be called before call")]
+fn call_without_readiness() {
+    let app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+    let mut service = ConcurrencyLimitLayer::new(1).layer(app);
+    drop(service.call(Scope::new("test".into()), stream::empty::<Event>()));
+}
+
+#[test]
+#[should_panic(expected = "concurrency limit must be greater than zero")]
+fn zero_limit() {
+    let app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+    let _ = ConcurrencyLimit::new(app, 0);
+}
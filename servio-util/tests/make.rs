@@ -0,0 +1,26 @@
+use futures_executor::block_on;
+use futures_util::future;
+use futures_util::stream;
+use http::{HeaderMap, StatusCode};
+use servio_service::{Event, MakeService};
+use servio_util::make::make_service_fn;
+use servio_util::response::PlainTextResponse;
+use std::convert::Infallible;
+
+type ServerStream = stream::Empty<Event>;
+
+#[test]
+fn make_service_fn_targets() {
+    let mut targets = Vec::new();
+    let mut factory = make_service_fn(|target: u16| {
+        targets.push(target);
+        let app = PlainTextResponse::new(StatusCode::OK, "".into(), HeaderMap::default());
+        future::ok::<_, Infallible>(app)
+    });
+
+    for target in [1, 2] {
+        let make = MakeService::<_, ServerStream>::make_service(&mut factory, target);
+        assert!(block_on(make).is_ok());
+    }
+    assert_eq!(targets, [1, 2]);
+}
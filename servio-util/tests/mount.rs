@@ -0,0 +1,61 @@
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, Iter};
+use http::{HeaderMap, StatusCode};
+use servio_http::http::{HttpEvent, HttpFamily, HttpScope, ResponseChunk, ResponseStart};
+use servio_service::{Event, Layer, Scope, Service};
+use servio_util::mount::{Mount, MountLayer};
+use servio_util::response::PlainTextResponse;
+use servio_util::test::TestClient;
+use std::convert::Infallible;
+use std::vec::IntoIter;
+
+/// Responds with `root_path`, app path and `path` of the request.
+#[derive(Clone)]
+struct Paths;
+
+impl<SS: Stream<Item = Event>> Service<SS> for Paths {
+    type AppStream = Iter<IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, _server_events: SS) -> Self::Future {
+        let http_scope = scope.get_ref::<HttpScope>().unwrap();
+        let mut chunk = ResponseChunk::default();
+        chunk.body = format!(
+            "{} {} {}",
+            http_scope.root_path,
+            http_scope.app_path(),
+            http_scope.path
+        )
+        .into();
+
+        let events = [
+            HttpEvent::ResponseStart(ResponseStart::default()),
+            HttpEvent::ResponseChunk(chunk),
+        ];
+        let events = events.into_iter().map(Event::typed::<HttpFamily>);
+        future::ok(stream::iter(events.collect::<Vec<_>>()))
+    }
+}
+
+#[test]
+fn nested_mounts() {
+    let not_found = PlainTextResponse::new(StatusCode::NOT_FOUND, "".into(), HeaderMap::new());
+    let api = Mount::new("/v1", Paths, not_found.clone());
+    let app = MountLayer::new("/api/", api).layer(not_found);
+    let mut client = TestClient::new(app);
+
+    let response = block_on(client.get("/api/v1/users?x=1").send()).unwrap();
+    assert_eq!(response.text(), "/api/v1 /users /api/v1/users");
+
+    let response = block_on(client.get("/api/v1").send()).unwrap();
+    assert_eq!(response.text(), "/api/v1 / /api/v1");
+
+    // Prefixes are matched by whole segments
+    let response = block_on(client.get("/api/v10").send()).unwrap();
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = block_on(client.get("/apis/v1").send()).unwrap();
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
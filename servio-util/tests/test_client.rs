@@ -0,0 +1,227 @@
+use bytes::{Bytes, BytesMut};
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, BoxFuture, FutureExt};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use servio_http::http::{
+    HttpEvent, HttpScope, ResponseChunk, ResponseStart, ResponseTrailer, EVENT_HTTP,
+};
+use servio_http::websocket::{Accept, TextFrame, WebSocketEvent, WebSocketScope, EVENT_WEBSOCKET};
+use servio_service::{Event, Scope, Service};
+use servio_util::response::PlainTextResponse;
+use servio_util::test::{TestClient, TestWebSocketResult};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Echoes request body in chunks, sending request path in trailers. Server stream is read until
+/// the end of the request body.
+#[derive(Clone)]
+struct EchoHttp;
+
+impl<SS> Service<SS> for EchoHttp
+where
+    SS: Stream<Item = Event> + Send + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        async move {
+            let http_scope = scope.get::<HttpScope>().unwrap();
+            let mut server_events = Box::pin(server_events);
+            let mut chunks: Vec<Bytes> = vec![];
+            while let Some(event) = server_events.next().await {
+                match event.get_ref::<HttpEvent>() {
+                    Some(HttpEvent::RequestChunk(chunk)) => {
+                        chunks.push(chunk.body.clone());
+                        if !chunk.more {
+                            break;
+                        }
+                    }
+                    // Trailers end the body
+                    _ => break,
+                }
+            }
+
+            let mut start = ResponseStart::default();
+            start.status = StatusCode::CREATED;
+            start.headers = http_scope.headers.clone();
+            start.trailers = true;
+
+            let mut events = vec![HttpEvent::ResponseStart(start)];
+            for (i, body) in chunks.iter().enumerate() {
+                let mut chunk = ResponseChunk::default();
+                chunk.body = body.clone();
+                chunk.more = i + 1 < chunks.len();
+                events.push(HttpEvent::ResponseChunk(chunk));
+            }
+
+            let mut trailer = ResponseTrailer::default();
+            let path = HeaderValue::from_str(http_scope.uri().path()).unwrap();
+            trailer.headers.insert("x-path", path);
+            events.push(HttpEvent::ResponseTrailer(trailer));
+
+            let events = events.into_iter().map(|e| Event::new(EVENT_HTTP.into(), e));
+            Ok(stream::iter(events).boxed())
+        }
+        .boxed()
+    }
+}
+
+/// Responds with empty body and keeps server stream for inspection.
+#[derive(Clone, Default)]
+struct KeepServerStream(Arc<Mutex<Option<BoxStream<'static, Event>>>>);
+
+impl<SS> Service<SS> for KeepServerStream
+where
+    SS: Stream<Item = Event> + Send + 'static,
+{
+    type AppStream = stream::Iter<std::vec::IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = future::Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, server_events: SS) -> Self::Future {
+        *self.0.lock().unwrap() = Some(server_events.boxed());
+        let events = [
+            HttpEvent::ResponseStart(ResponseStart::default()),
+            HttpEvent::ResponseChunk(ResponseChunk::default()),
+        ];
+        let events = events.into_iter().map(|e| Event::new(EVENT_HTTP.into(), e));
+        future::ok(stream::iter(events.collect::<Vec<_>>()))
+    }
+}
+
+/// Accepts WebSocket connection with the first requested subprotocol and echoes frames.
+#[derive(Clone)]
+struct EchoWebSocket;
+
+impl<SS> Service<SS> for EchoWebSocket
+where
+    SS: Stream<Item = Event> + Send + 'static,
+{
+    type AppStream = BoxStream<'static, Event>;
+    type Error = Infallible;
+    type Future = future::Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, scope: Scope, server_events: SS) -> Self::Future {
+        let ws_scope = scope.get::<WebSocketScope>().unwrap();
+        let mut accept = Accept::default();
+        accept.subprotocol = ws_scope.subprotocols.first().cloned();
+
+        let echo = server_events
+            .take_while(|e| {
+                let disconnect = matches!(
+                    e.get_ref::<WebSocketEvent>(),
+                    Some(WebSocketEvent::Disconnect(..))
+                );
+                future::ready(!disconnect)
+            })
+            .filter(|e| {
+                let frame = matches!(
+                    e.get_ref::<WebSocketEvent>(),
+                    Some(WebSocketEvent::TextFrame(..) | WebSocketEvent::BinaryFrame(..))
+                );
+                future::ready(frame)
+            });
+
+        let accept = Event::new(EVENT_WEBSOCKET.into(), WebSocketEvent::Accept(accept));
+        future::ok(stream::once(future::ready(accept)).chain(echo).boxed())
+    }
+}
+
+#[test]
+fn plain_text_response() {
+    let app = PlainTextResponse::new(StatusCode::OK, "Hello".into(), HeaderMap::default());
+    let mut client = TestClient::new(app);
+
+    let response = block_on(client.get("/").send()).unwrap();
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["content-type"], "text/plain");
+    assert_eq!(response.text(), "Hello");
+    assert!(response.trailers.is_none());
+}
+
+#[test]
+fn chunked_request_with_trailers() {
+    let mut client = TestClient::new(EchoHttp);
+
+    let response = block_on(
+        client
+            .post("/echo?x=1")
+            .header("x-test", "yes")
+            .chunks(["Hello, ", "world!"])
+            .send(),
+    )
+    .unwrap();
+
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(response.headers["x-test"], "yes");
+    assert_eq!(response.body, BytesMut::from("Hello, world!"));
+    assert_eq!(response.trailers.unwrap()["x-path"], "/echo");
+}
+
+#[test]
+fn disconnect_after_response() {
+    let app = KeepServerStream::default();
+    let mut client = TestClient::new(app.clone());
+    block_on(client.post("/").body("Hello").send()).unwrap();
+
+    let server_events = app.0.lock().unwrap().take().unwrap();
+    let events: Vec<_> = block_on(server_events.collect());
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        events[1].get_ref::<HttpEvent>(),
+        Some(HttpEvent::Disconnect(..))
+    ));
+}
+
+#[test]
+fn websocket_session() {
+    let mut client = TestClient::new(EchoWebSocket);
+
+    block_on(async {
+        let connection = client
+            .websocket("/ws")
+            .subprotocol("chat")
+            .subprotocol("json")
+            .connect()
+            .await
+            .unwrap();
+
+        let mut session = connection.unwrap();
+        assert_eq!(session.accept().subprotocol.as_deref(), Some("chat"));
+
+        session.send_text("ping");
+        assert_eq!(session.receive_text().await, "ping");
+
+        session.send_binary(&b"\x00\x01"[..]);
+        assert_eq!(session.receive_binary().await, &b"\x00\x01"[..]);
+
+        // Fragments are joined into a message
+        for (data, more) in [("Hello, ", true), ("world!", false)] {
+            let mut frame = TextFrame::default();
+            frame.data = data.into();
+            frame.more = more;
+            let event = Event::new(EVENT_WEBSOCKET.into(), WebSocketEvent::TextFrame(frame));
+            session.send_event(event);
+        }
+        assert_eq!(session.receive_text().await, "Hello, world!");
+
+        session.disconnect(1000);
+    });
+}
+
+#[test]
+fn websocket_denied() {
+    let app = PlainTextResponse::new(StatusCode::FORBIDDEN, "Go away".into(), HeaderMap::new());
+    let mut client = TestClient::new(app);
+
+    let connection = block_on(client.websocket("/ws").connect()).unwrap();
+    let TestWebSocketResult::Denied(response) = connection else {
+        panic!("connection must be denied");
+    };
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.text(), "Go away");
+}
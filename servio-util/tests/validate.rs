@@ -0,0 +1,145 @@
+use futures_core::Stream;
+use futures_executor::block_on;
+use futures_util::future::{self, Ready};
+use futures_util::stream::{self, StreamExt};
+use servio_http::http::{HttpEvent, ResponseChunk, ResponseStart, EVENT_HTTP, PROTOCOL_HTTP};
+use servio_http::websocket::{
+    Accept, BinaryFrame, Close, Ping, TextFrame, WebSocketEvent, EVENT_WEBSOCKET,
+    PROTOCOL_WEBSOCKET,
+};
+use servio_service::{Event, Scope, Service};
+use servio_util::validate::{ProtocolViolation, Validate};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+/// Application, that sends scripted events.
+#[derive(Clone)]
+struct Script(Vec<Event>);
+
+impl<SS: Stream<Item = Event>> Service<SS> for Script {
+    type AppStream = stream::Iter<std::vec::IntoIter<Event>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::AppStream, Self::Error>>;
+
+    fn call(&mut self, _scope: Scope, _server_events: SS) -> Self::Future {
+        future::ok(stream::iter(self.0.clone()))
+    }
+}
+
+fn http(event: HttpEvent) -> Event {
+    Event::new(EVENT_HTTP.into(), event)
+}
+
+fn ws(event: WebSocketEvent) -> Event {
+    Event::new(EVENT_WEBSOCKET.into(), event)
+}
+
+fn start() -> Event {
+    http(HttpEvent::ResponseStart(ResponseStart::default()))
+}
+
+fn chunk(more: bool) -> Event {
+    let mut chunk = ResponseChunk::default();
+    chunk.more = more;
+    http(HttpEvent::ResponseChunk(chunk))
+}
+
+/// Runs scripted events through `Validate`, returns the number of passed events and violations.
+fn validate(protocol: &'static str, events: Vec<Event>) -> (usize, Vec<ProtocolViolation>) {
+    let violations = Arc::new(Mutex::new(vec![]));
+    let handler = {
+        let violations = violations.clone();
+        move |v| violations.lock().unwrap().push(v)
+    };
+
+    let mut service = Validate::with_handler(Script(events), handler);
+    let scope = Scope::new(protocol.into());
+    let app_stream = block_on(service.call(scope, stream::empty())).unwrap();
+    let passed = block_on(app_stream.count());
+
+    let violations = violations.lock().unwrap().clone();
+    (passed, violations)
+}
+
+#[test]
+fn valid_http_response() {
+    let events = vec![start(), chunk(true), chunk(false)];
+    assert_eq!(validate(PROTOCOL_HTTP, events), (3, vec![]));
+}
+
+#[test]
+fn chunk_before_start() {
+    let events = vec![chunk(false), start()];
+    let violation = ProtocolViolation::UnexpectedEvent {
+        expected: "ResponseStart",
+        got: "ResponseChunk",
+    };
+    assert_eq!(validate(PROTOCOL_HTTP, events), (0, vec![violation]));
+}
+
+#[test]
+fn chunk_after_end() {
+    let events = vec![start(), chunk(false), chunk(false)];
+    let violation = ProtocolViolation::EventAfterEnd {
+        got: "ResponseChunk",
+    };
+    assert_eq!(validate(PROTOCOL_HTTP, events), (2, vec![violation]));
+}
+
+#[test]
+fn incomplete_response() {
+    let events = vec![start(), chunk(true)];
+    let violation = ProtocolViolation::UnexpectedEnd {
+        expected: "ResponseChunk",
+    };
+    assert_eq!(validate(PROTOCOL_HTTP, events), (2, vec![violation]));
+}
+
+#[test]
+fn websocket_double_accept() {
+    let events = vec![
+        ws(WebSocketEvent::Accept(Accept::default())),
+        ws(WebSocketEvent::TextFrame(TextFrame::default())),
+        ws(WebSocketEvent::Accept(Accept::default())),
+    ];
+    let violation = ProtocolViolation::UnexpectedEvent {
+        expected: "TextFrame, BinaryFrame, Ping, Pong or Close",
+        got: "Accept",
+    };
+    assert_eq!(validate(PROTOCOL_WEBSOCKET, events), (2, vec![violation]));
+}
+
+#[test]
+fn websocket_interleaved_fragments() {
+    let text = |more| {
+        let mut frame = TextFrame::default();
+        frame.more = more;
+        ws(WebSocketEvent::TextFrame(frame))
+    };
+    let events = vec![
+        ws(WebSocketEvent::Accept(Accept::default())),
+        text(true),
+        ws(WebSocketEvent::Ping(Ping::default())),
+        text(false),
+        text(true),
+        ws(WebSocketEvent::BinaryFrame(BinaryFrame::default())),
+    ];
+    let violation = ProtocolViolation::UnexpectedEvent {
+        expected: "TextFrame",
+        got: "BinaryFrame",
+    };
+    assert_eq!(validate(PROTOCOL_WEBSOCKET, events), (5, vec![violation]));
+}
+
+#[test]
+fn websocket_denial() {
+    let events = vec![start(), chunk(false)];
+    assert_eq!(validate(PROTOCOL_WEBSOCKET, events), (2, vec![]));
+
+    let events = vec![
+        ws(WebSocketEvent::Close(Close::default())),
+        ws(WebSocketEvent::TextFrame(TextFrame::default())),
+    ];
+    let violation = ProtocolViolation::EventAfterEnd { got: "TextFrame" };
+    assert_eq!(validate(PROTOCOL_WEBSOCKET, events), (1, vec![violation]));
+}